@@ -1,12 +1,41 @@
-use std::{collections::HashSet, path::Path, process::exit};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashSet},
+    ffi::OsString,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::exit,
+};
 
-use clap::ArgMatches;
-use inquire::{autocompletion::Replacement, validator::Validation, Autocomplete, Select, Text};
-use time::OffsetDateTime;
+use clap::{parser::ValueSource, ArgMatches};
+use inquire::{
+    autocompletion::Replacement, error::InquireResult, validator::Validation, Autocomplete,
+    Confirm, InquireError, MultiSelect, Select, Text,
+};
+use regex::Regex;
+use time::{Date, Duration, OffsetDateTime};
 
 use crate::{
-    config::Config,
-    project::{Project, ProjectManager, SortOrder},
+    archive::ARCHIVE_DIR,
+    backup, cli, clipboard,
+    config::{self, Cleaner, Config, Crypt, TagRules},
+    crypt, dashboard,
+    disk::{self, SizeCache},
+    error::{self, ProjectErrorTypes, Severity},
+    executor, export,
+    format::{self, Column, Field, OutputFormat, ProjectEntry, Table, TimeStyle},
+    git::{self, GitStatus},
+    history, index, plugin,
+    progress::Progress,
+    project::{self, Archived, Project, ProjectManager, SortOrder, Substitution},
+    registry::Registry,
+    scripting::Hooks,
+    serve, stats,
+    storage::{Disk, Storage},
+    tasks,
+    timing::Timings,
+    trash, workspace,
 };
 
 #[derive(Clone)]
@@ -21,13 +50,26 @@ impl Suggester {
 }
 
 impl Autocomplete for Suggester {
+    /// Tags matching `input` case-insensitively: prefixes first, then tags
+    /// containing it and then tags containing its characters in order.
     fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
-        Ok(self
+        let input = input.to_lowercase();
+        let mut res: Vec<(u8, &String)> = self
             .tags
-            .clone()
-            .into_iter()
-            .filter(|t| t.starts_with(&input.to_lowercase()))
-            .collect::<Vec<_>>())
+            .iter()
+            .filter_map(|t| {
+                let tag = t.to_lowercase();
+                let rank = match true {
+                    true if tag.starts_with(&input) => 0,
+                    true if tag.contains(&input) => 1,
+                    true if is_subsequence(&input, &tag) => 2,
+                    _ => return None,
+                };
+                Some((rank, t))
+            })
+            .collect();
+        res.sort();
+        Ok(res.into_iter().map(|(_, t)| t.to_owned()).collect())
     }
     fn get_completion(
         &mut self,
@@ -38,7 +80,13 @@ impl Autocomplete for Suggester {
     }
 }
 
-fn handle_result<T>(res: Result<T, String>) -> T {
+/// Whether all characters of `needle` appear in `haystack` in order.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
+}
+
+fn handle_result<T, E: Display>(res: Result<T, E>) -> T {
     match res {
         Err(e) => {
             eprintln!("ERROR: {}", e);
@@ -48,26 +96,81 @@ fn handle_result<T>(res: Result<T, String>) -> T {
     }
 }
 
-fn choose_tags(manager: &mut ProjectManager, tags: &mut HashSet<String>) {
+/// Answers of interactive prompts. Esc gives `None`, while Ctrl-C, a closed
+/// stdin or a terminal that can't be used exit instead of panicking.
+trait Answer<T> {
+    fn answer(self) -> Option<T>;
+}
+
+impl<T> Answer<T> for InquireResult<Option<T>> {
+    fn answer(self) -> Option<T> {
+        match self {
+            Ok(answer) => answer,
+            Err(InquireError::OperationInterrupted) => exit(130),
+            Err(e) => handle_result(Err(format!("Couldn't prompt: {}", e))),
+        }
+    }
+}
+
+/// Makes panics leave the terminal usable: raw mode is turned off and the
+/// cursor shown again before the message is printed.
+pub fn guard_terminal() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::io::stderr().is_terminal() {
+            let _ = crossterm::terminal::disable_raw_mode();
+            let _ = crossterm::execute!(std::io::stderr(), crossterm::cursor::Show);
+        }
+        default(info);
+    }));
+}
+
+const NEW_TAG: &str = "add new tag…";
+
+/// Lets the user check/uncheck known tags in one list, then enter new ones
+/// if "add new tag…" was checked. Esc keeps `tags` as they were.
+fn choose_tags(manager: &mut ProjectManager, rules: &TagRules, tags: &mut HashSet<String>) {
+    let mut known: Vec<String> = manager.tags().iter().cloned().collect();
+    if known.is_empty() {
+        return enter_tags(manager, rules, tags);
+    }
+    known.sort();
+    let checked: Vec<usize> = (0..known.len())
+        .filter(|i| tags.contains(&known[*i]))
+        .collect();
+    known.push(NEW_TAG.to_owned());
+    let selected = MultiSelect::new("Select tags:", known)
+        .with_default(&checked)
+        .with_help_message("Space to toggle, Enter to confirm, Esc to cancel")
+        .prompt_skippable()
+        .answer();
+    if let Some(selected) = selected {
+        let add_new = selected.iter().any(|t| t == NEW_TAG);
+        *tags = selected.into_iter().filter(|t| t != NEW_TAG).collect();
+        if add_new {
+            enter_tags(manager, rules, tags);
+        }
+    }
+}
+
+fn enter_tags(manager: &mut ProjectManager, rules: &TagRules, tags: &mut HashSet<String>) {
     loop {
         //let help_msg = tags.clone().into_iter().collect::<Vec<String>>().join(", ");
         let help_msg = "Press Esc to finish";
         println!("current tags: {:?}", tags);
         let tag = Text::new("Enter a tag to add or remove:")
             .with_help_message(help_msg)
-            .with_autocomplete(Suggester::new(manager.get_tags()))
-            .with_validator(|tag: &str| {
-                if tag.contains(char::is_whitespace) {
-                    return Ok(Validation::Invalid(
-                        "Tag shouldn't contain whitespace".into(),
-                    ));
+            .with_autocomplete(Suggester::new(manager.tags().clone()))
+            .with_validator({
+                let rules = rules.clone();
+                move |tag: &str| match rules.check(tag) {
+                    Ok(()) => Ok(Validation::Valid),
+                    Err(e) => Ok(Validation::Invalid(e.into())),
                 }
-                Ok(Validation::Valid)
             })
-            .with_formatter(&|s: &str| s.to_lowercase())
             .prompt_skippable()
-            .unwrap();
-        match tag {
+            .answer();
+        match tag.map(|tag| manager.normalize_tag(&tag)) {
             Some(tag) => {
                 if tags.contains(&tag) {
                     tags.remove(&tag);
@@ -84,91 +187,2060 @@ fn choose_tags(manager: &mut ProjectManager, tags: &mut HashSet<String>) {
     }
 }
 
-fn create(mut manager: ProjectManager, args: &ArgMatches) {
-    let mut tags = HashSet::<String>::new();
-    let name: &String = args.get_one::<String>("project-name").unwrap();
+fn create(
+    mut manager: ProjectManager,
+    hooks: &Hooks,
+    rules: &TagRules,
+    slugify: bool,
+    name_pattern: Option<&Regex>,
+    auto_name: impl FnOnce(&ProjectManager) -> String,
+    args: &ArgMatches,
+) {
+    let auto = args.get_flag("auto").then(|| auto_name(&manager));
+    let title = args.get_one::<String>("project-name").or(auto.as_ref());
+    let title = handle_result(title.ok_or("New projects need a name instead of --id"));
+    let slug = project::slugify(title);
+    let name = if slugify { &slug } else { title };
+    if name.is_empty() {
+        handle_result::<(), _>(Err(format!("'{}' can't be used as a name", title)));
+    }
+    if let Some(pattern) = name_pattern.filter(|p| !p.is_match(name)) {
+        handle_result::<(), _>(Err(format!(
+            "'{}' doesn't match name_pattern '{}' of the config",
+            name, pattern
+        )));
+    }
     if manager.get_mut_project(name).is_ok() {
         eprintln!("Such project already exists");
         return;
     }
-    choose_tags(&mut manager, &mut tags);
-    let project = Project::new(name.to_owned(), OffsetDateTime::now_utc(), tags);
-    handle_result(manager.create(project));
+    let from = args.get_one::<PathBuf>("from");
+    if let Some(from) = from.filter(|f| !f.is_dir()) {
+        handle_result::<(), _>(Err(format!("{:?} isn't a directory", from)));
+    }
+    let mut tags = match tags_arg(rules, args) {
+        Some(tags) => tags,
+        None => {
+            let mut tags = HashSet::new();
+            choose_tags(&mut manager, rules, &mut tags);
+            tags
+        }
+    };
+    let mut project = Project::new(name.to_owned(), OffsetDateTime::now_utc(), tags);
+    let extra = handle_result(hooks.extra_tags(&project, &manager.get_path(name)));
+    tags = project.get_tags();
+    tags.extend(extra);
+    let tags = rules.imply(&HashSet::new(), tags);
+    project = Project::new(name.to_owned(), project.get_created(), tags);
+    if name != title {
+        project = project.with_title(title.to_owned());
+    }
+    if let Some(user) = project::current_user() {
+        project = project.with_owner(user);
+    }
+    match from {
+        Some(from) => handle_result(manager.create_from(project, from, args.get_flag("copy"))),
+        None => handle_result(manager.create(project)),
+    }
+    let tags = history::tags(&manager.get_project(name).unwrap().get_tags());
+    history::record("create", name, "", &tags);
+    if auto.is_some() {
+        println!("{}", name);
+    }
+}
+
+fn init(mut manager: ProjectManager, hooks: &Hooks, rules: &TagRules, args: &ArgMatches) {
+    let cwd = handle_result(std::env::current_dir().map_err(|e| e.to_string()));
+    let cwd = cwd.canonicalize().unwrap_or(cwd);
+    let name = match args.get_one::<String>("name") {
+        Some(name) => name.to_owned(),
+        None => cwd
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+    if name.is_empty() {
+        handle_result::<(), _>(Err("Couldn't infer project name, use --name"));
+    }
+    if manager.get_mut_project(&name).is_ok() {
+        eprintln!("Such project already exists");
+        return;
+    }
+    let mut tags = tags_arg(rules, args).unwrap_or_else(|| {
+        let mut tags = HashSet::new();
+        choose_tags(&mut manager, rules, &mut tags);
+        tags
+    });
+    tags.extend(handle_result(hooks.extra_tags(
+        &Project::new(name.clone(), OffsetDateTime::now_utc(), tags.clone()),
+        &cwd,
+    )));
+    let tags = rules.imply(&HashSet::new(), tags);
+    let mut project = Project::new(name.clone(), OffsetDateTime::now_utc(), tags);
+    if let Some(user) = project::current_user() {
+        project = project.with_owner(user);
+    }
+    handle_result(manager.register(project, cwd));
+    let tags = history::tags(&manager.get_project(&name).unwrap().get_tags());
+    history::record("init", &name, "", &tags);
+}
+
+/// Tags given with `--tags`, exiting if one of them breaks `rules`.
+/// None when the flag is missing so tags are asked interactively.
+fn tags_arg(rules: &TagRules, args: &ArgMatches) -> Option<HashSet<String>> {
+    let tags = args.get_many::<String>("tags")?;
+    Some(
+        tags.filter(|t| !t.is_empty())
+            .map(|t| {
+                handle_result(rules.check(t));
+                t.to_owned()
+            })
+            .collect(),
+    )
+}
+
+/// Name of the project given as argument `id`. The pseudo-name `.` refers to
+/// the project containing current directory, paths(with a `/`) to the project
+/// containing them and partial names are resolved when they match only one
+/// project.
+fn project_name(manager: &ProjectManager, args: &ArgMatches, id: &str) -> String {
+    if let Some(uuid) = args.get_one::<String>("id") {
+        return handle_result(manager.find_id(uuid));
+    }
+    let name = args.get_one::<String>(id).unwrap();
+    if name == "." {
+        return handle_result(manager.current());
+    }
+    handle_result(manager.resolve_name(name))
+}
+
+fn rename_project(manager: &mut ProjectManager, src: &str, dst: &str) {
+    handle_result(manager.rename(src, dst));
+    history::record("rename", src, src, dst);
+}
+
+fn modify_tags(
+    manager: &mut ProjectManager,
+    rules: &TagRules,
+    name: &str,
+    mut tags: HashSet<String>,
+) {
+    let before = tags.clone();
+    choose_tags(manager, rules, &mut tags);
+    handle_result(manager.modify(name, rules.imply(&before, tags)));
+    record_tags(manager, name, &before);
+}
+
+/// Records the change of tags of `name` from `old` in history and prints it
+/// like "name: +cli  -python".
+fn record_tags(manager: &ProjectManager, name: &str, old: &HashSet<String>) {
+    let new = manager.get_project(name).unwrap().get_tags();
+    let diff = history::record_tags("modify", name, old, &new);
+    if !diff.is_empty() {
+        println!("{}: {}", name, diff);
+    }
+}
+
+const ANOTHER_NAME: &str = "Choose another name";
+const MERGE: &str = "Merge them";
+const ABORT: &str = "Abort";
+
+/// Renames `src` to `dst` like [`rename_project`]. If the name is taken,
+/// `-1`, `-2`… is appended to it with `suffix`, otherwise the user can choose
+/// another name, merge the two or give up when asking is possible.
+fn rename_resolving(manager: &mut ProjectManager, src: &str, dst: &str, suffix: bool) {
+    let mut name = dst.to_owned();
+    for n in 1.. {
+        let err = match manager.rename(src, &name) {
+            Ok(()) => return history::record("rename", src, src, &name),
+            Err(e) => e,
+        };
+        let taken = matches!(
+            err.kind,
+            ProjectErrorTypes::DuplicateName | ProjectErrorTypes::NameCollision
+        );
+        if taken && suffix {
+            name = format!("{}-{}", dst, n);
+            continue;
+        }
+        if !taken || !std::io::stdin().is_terminal() {
+            return handle_result::<(), _>(Err(err));
+        }
+        eprintln!("ERROR: {}", err);
+        let mut options = vec![ANOTHER_NAME, ABORT];
+        if err.kind == ProjectErrorTypes::DuplicateName {
+            options.insert(1, MERGE);
+        }
+        match Select::new("What now?", options)
+            .prompt_skippable()
+            .answer()
+        {
+            Some(ANOTHER_NAME) => match Text::new("New name:").prompt_skippable().answer() {
+                Some(another) => name = another,
+                None => exit(-1),
+            },
+            Some(MERGE) => {
+                let command = match manager.get_project(&name) {
+                    Some(_) => "merge",
+                    None => "rename",
+                };
+                handle_result(manager.merge(src, &name));
+                return history::record(command, src, src, &name);
+            }
+            _ => exit(-1),
+        }
+    }
 }
 
 fn rename(mut manager: ProjectManager, args: &ArgMatches) {
-    handle_result(manager.rename(
-        args.get_one::<String>("project-name").unwrap(),
-        args.get_one::<String>("new-name").unwrap(),
-    ));
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    if let Some(substitution) = args.get_one::<Substitution>("pattern") {
+        return rename_all(manager, substitution, args);
+    }
+    let name = project_name(&manager, args, "project-name");
+    // with --id the only name given is the new one
+    let new_name = args
+        .get_one::<String>("new-name")
+        .or(args.get_one::<String>("project-name"));
+    let new_name = handle_result(new_name.ok_or("Missing the new name"));
+    rename_resolving(&mut manager, &name, new_name, args.get_flag("suffix"));
 }
 
-fn modify(mut manager: ProjectManager, args: &ArgMatches) {
-    let name = args.get_one::<String>("project-name").unwrap();
-    let project = handle_result(manager.get_mut_project(name));
-    let mut tags = project.get_tags();
-    choose_tags(&mut manager, &mut tags);
-    handle_result(manager.modify(name, tags));
+/// Renames every project `substitution` matches after showing old and new
+/// names, refusing the whole batch if any of them can't be renamed.
+fn rename_all(mut manager: ProjectManager, substitution: &Substitution, args: &ArgMatches) {
+    let tags: Vec<String> = args
+        .get_many::<String>("tag")
+        .map(|tags| tags.map(|t| manager.normalize_tag(t)).collect())
+        .unwrap_or_default();
+    let renames: Vec<(String, String)> = manager
+        .iter_projects(SortOrder::Name)
+        .filter(|p| tags.iter().all(|t| p.get_tags().contains(t)))
+        .filter_map(|p| Some((p.get_name().to_owned(), substitution.apply(p.get_name())?)))
+        .filter(|(old, new)| old != new)
+        .collect();
+    if renames.is_empty() {
+        println!("No project names match the pattern");
+        return;
+    }
+    let width = renames
+        .iter()
+        .map(|(old, _)| format::display_width(old))
+        .max()
+        .unwrap();
+    for (old, new) in &renames {
+        println!("{}  →  {}", format::pad(old, width), new);
+    }
+    handle_result(manager.check_renames(&renames));
+    if args.get_flag("dry-run") {
+        return;
+    }
+    let confirmed = Confirm::new(&format!("Rename {} project(s)?", renames.len()))
+        .with_default(false)
+        .prompt_skippable()
+        .answer();
+    if confirmed != Some(true) {
+        return;
+    }
+    for (old, new) in renames {
+        rename_project(&mut manager, &old, &new);
+    }
+}
+
+fn modify(mut manager: ProjectManager, rules: &TagRules, crypt: Option<&Crypt>, args: &ArgMatches) {
+    let name = &project_name(&manager, args, "project-name");
+    let project = handle_result(manager.get_mut_project(name)).clone();
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    if args.get_one::<bool>("locked") == Some(&false) && project.is_locked() {
+        handle_result(manager.set_locked(name, false));
+        history::record("unlock", name, "", "");
+    }
+    if args.get_flag("hide") || args.get_flag("unhide") {
+        let hidden = args.get_flag("hide");
+        handle_result(manager.set_hidden(name, hidden));
+        if hidden != project.is_hidden() {
+            history::record(if hidden { "hide" } else { "unhide" }, name, "", "");
+        }
+    }
+    if args.get_flag("encrypt") || args.get_flag("decrypt") {
+        let crypt = handle_result(crypt.ok_or("Set crypt in the config to encrypt metadata"));
+        match args.get_flag("encrypt") {
+            true => {
+                handle_result(manager.seal(name, |text| crypt::encrypt(crypt, text)));
+                history::record("encrypt", name, "", "");
+            }
+            false => {
+                handle_result(manager.unseal(name, |text| crypt::decrypt(crypt, text)));
+                history::record("decrypt", name, "", "");
+            }
+        }
+    }
+    if args.contains_id("priority") {
+        let priority = match args.get_one::<u8>("priority") {
+            Some(priority) => Some(*priority),
+            None => choose_priority(project.get_priority()),
+        };
+        handle_result(manager.set_priority(name, priority));
+        let show = |p: Option<u8>| p.map(|p| p.to_string()).unwrap_or_default();
+        if priority != project.get_priority() {
+            history::record(
+                "priority",
+                name,
+                &show(project.get_priority()),
+                &show(priority),
+            );
+        }
+    }
+    if let Some(owner) = args.get_one::<Option<String>>("owner") {
+        handle_result(manager.set_owner(name, owner.clone()));
+        if owner.as_ref() != project.get_owner() {
+            let show = |o: Option<&String>| o.cloned().unwrap_or_default();
+            history::record(
+                "owner",
+                name,
+                &show(project.get_owner()),
+                &show(owner.as_ref()),
+            );
+        }
+    }
+    if let Some(completed) = args.get_one::<Option<Date>>("completed") {
+        handle_result(manager.set_completed(name, *completed));
+        let show = |d: Option<Date>| d.map(format::format_date).unwrap_or_default();
+        if *completed != project.get_completed() {
+            history::record(
+                "completed",
+                name,
+                &show(project.get_completed()),
+                &show(*completed),
+            );
+        }
+    }
+    if let Some(due) = args.get_one::<Option<Date>>("due") {
+        handle_result(manager.set_due(name, *due));
+        let show = |d: Option<Date>| d.map(format::format_date).unwrap_or_default();
+        if *due != project.get_due() {
+            history::record("due", name, &show(project.get_due()), &show(*due));
+        }
+    }
+    match tags_arg(rules, args) {
+        Some(tags) => {
+            handle_result(manager.modify(name, rules.imply(&project.get_tags(), tags)));
+            record_tags(&manager, name, &project.get_tags());
+        }
+        None if args.contains_id("priority")
+            || args.contains_id("due")
+            || args.contains_id("completed")
+            || args.contains_id("owner")
+            || args.contains_id("locked")
+            || args.get_flag("hide")
+            || args.get_flag("unhide")
+            || args.get_flag("encrypt")
+            || args.get_flag("decrypt") => {}
+        None => modify_tags(&mut manager, rules, name, project.get_tags()),
+    }
+    if args.get_one::<bool>("locked") == Some(&true) && !project.is_locked() {
+        handle_result(manager.set_locked(name, true));
+        history::record("lock", name, "", "");
+    }
+}
+
+/// Prints the title and custom values of an encrypted project, leaving them
+/// encrypted on disk.
+fn reveal(manager: ProjectManager, crypt: Option<&Crypt>, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    let crypt = handle_result(crypt.ok_or("Set crypt in the config to decrypt metadata"));
+    let project = manager.get_project(&name).unwrap();
+    let project = handle_result(project.unsealed(|text| crypt::decrypt(crypt, text)));
+    if let Some(title) = project.get_title() {
+        println!("title: {}", title);
+    }
+    let path = manager.get_path(&name);
+    for key in project.get_all_custom().keys() {
+        let value = Field::Custom(key.to_owned()).value(&project, &path);
+        println!("{}: {}", key, value);
+    }
+}
+
+/// Asks for a priority, empty meaning none. Esc keeps `current`.
+fn choose_priority(current: Option<u8>) -> Option<u8> {
+    let current_text = current.map(|p| p.to_string()).unwrap_or_default();
+    let answer = Text::new("Priority:")
+        .with_initial_value(&current_text)
+        .with_help_message("a number from 0 to 255, higher is more important. empty for none")
+        .with_validator(
+            |s: &str| match s.trim().is_empty() || s.trim().parse::<u8>().is_ok() {
+                true => Ok(Validation::Valid),
+                false => Ok(Validation::Invalid("Not a number from 0 to 255".into())),
+            },
+        )
+        .prompt_skippable()
+        .answer();
+    match answer {
+        Some(answer) => answer.trim().parse().ok(),
+        None => current,
+    }
 }
 
-fn exec(manager: ProjectManager, default_executor: String, args: &ArgMatches) {
-    handle_result(manager.exec(
-        args.get_one::<String>("project-name").unwrap(),
-        default_executor,
-        args.get_one::<String>("command").unwrap(),
-    ));
+fn exec(mut manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    let name = &project_name(&manager, args, "project-name");
+    let mut cmd = args.get_one::<String>("command").unwrap().to_owned();
+    let last = handle_result(manager.get_mut_project(name))
+        .get_last_command()
+        .cloned();
+    if args.get_flag("again") {
+        cmd = handle_result(last.ok_or_else(|| format!("No command was run in '{}' yet", name)));
+    } else if let Some(last) = last.filter(|_| {
+        cmd.is_empty()
+            && !args.contains_id("preset")
+            && !args.get_flag("explain")
+            && std::io::stdin().is_terminal()
+    }) {
+        cmd = match Text::new("Command:")
+            .with_initial_value(&last)
+            .with_help_message("empty opens the project, Esc cancels")
+            .prompt_skippable()
+            .answer()
+        {
+            Some(answer) => answer.trim().to_owned(),
+            None => return,
+        };
+    }
+    if !cmd.is_empty() && !args.get_flag("explain") {
+        handle_result(manager.set_last_command(name, &cmd));
+    }
+    if args.get_one::<String>("preset").is_some() {
+        handle_result(manager.get_mut_project(name));
+        let file = handle_result(workspace::default_file(&[name]));
+        handle_result(workspace::write(&[(name, manager.get_path(name))], &file));
+        cmd = format!("code {}", file.to_string_lossy());
+    }
+    let subdir = args.get_one::<PathBuf>("cd");
+    if args.get_flag("explain") {
+        let tags = handle_result(manager.get_mut_project(name)).get_tags();
+        if cmd.is_empty() {
+            cmd = executor::program(conf, &tags);
+        }
+        let mut path = manager.get_path(name);
+        if let Some(subdir) = subdir {
+            path = handle_result(project::subdirectory(&path, subdir));
+        }
+        print!("{}", project::explain(&path, &cmd));
+        return;
+    }
+    exec_project(manager, name, None, subdir.map(|s| s.as_path()), conf, &cmd);
+}
+
+/// Runs `cmd` in project `name` like [`ProjectManager::exec_in`], the program
+/// of its tags(see [`executor::program`]) if `cmd` is empty. If the project
+/// directory disappeared, offers to forget the project.
+fn exec_project(
+    manager: ProjectManager,
+    name: &str,
+    worktree: Option<&str>,
+    subdir: Option<&Path>,
+    conf: &Config,
+    cmd: &str,
+) {
+    let (root, path) = (manager.get_root().to_owned(), manager.get_path(name));
+    let tags = manager
+        .get_project(name)
+        .map(|p| p.get_tags())
+        .unwrap_or_default();
+    let program = executor::program(conf, &tags);
+    let err = match manager.exec_in(name, worktree, subdir, program, cmd) {
+        Ok(()) => return,
+        Err(e) => e,
+    };
+    if err.kind == ProjectErrorTypes::NonExistingProject && !path.exists() {
+        eprintln!("ERROR: {}", err);
+        // not asking(or failing to) keeps it
+        let forget = Confirm::new(&format!("Forget '{}'?", name))
+            .with_default(true)
+            .prompt_skippable()
+            .ok()
+            .flatten();
+        if forget == Some(true) {
+            handle_result(project::forget(&root, name, &path));
+        }
+        exit(-1);
+    }
+    handle_result::<(), _>(Err(err));
+}
+
+fn random(manager: ProjectManager, conf: &Config, style: &TimeStyle, args: &ArgMatches) {
+    let tags: Vec<String> = args
+        .get_many::<String>("tag")
+        .map(|tags| tags.map(|t| manager.normalize_tag(t)).collect())
+        .unwrap_or_default();
+    // randomly seeded hashers are random enough to pick a project
+    let roll = RandomState::new().build_hasher().finish() as f64 / (u64::MAX as f64 + 1.0);
+    let project = manager
+        .pick_weighted(|p| tags.iter().all(|t| p.get_tags().contains(t)), roll)
+        .cloned();
+    let project = handle_result(project.ok_or("No projects to pick from"));
+    if args.get_flag("print") {
+        println!("{}", project.get_name());
+        return;
+    }
+    println!(
+        "{} (last accessed {})",
+        project,
+        style.display(project.get_accessed())
+    );
+    exec_project(manager, project.get_name(), None, None, conf, "");
+}
+
+fn run(manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    let path = manager.get_path(&name);
+    let mut tasks = tasks::discover(&path);
+    if tasks.is_empty() {
+        handle_result::<(), _>(Err(format!(
+            "No justfile, Makefile or package.json scripts found in {:?}",
+            path
+        )));
+    }
+    let task = match args.get_one::<String>("task") {
+        Some(task) => match tasks.iter().position(|t| t.name == *task) {
+            Some(idx) => tasks.swap_remove(idx),
+            None => handle_result(Err(format!(
+                "No task '{}' in {}(available: {})",
+                task,
+                name,
+                tasks
+                    .iter()
+                    .map(|t| t.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        },
+        None => match Select::new("Choose a task:", tasks)
+            .prompt_skippable()
+            .answer()
+        {
+            Some(task) => task,
+            None => return,
+        },
+    };
+    exec_project(manager, &name, None, None, conf, &task.command());
+}
+
+fn make_workspace(mut manager: ProjectManager, args: &ArgMatches) {
+    let by_id = args
+        .contains_id("id")
+        .then(|| project_name(&manager, args, "id"));
+    let names: Vec<&String> = match &by_id {
+        Some(name) => vec![name],
+        None => args.get_many::<String>("project-name").unwrap().collect(),
+    };
+    let mut folders = Vec::new();
+    for name in &names {
+        handle_result(manager.get_mut_project(name));
+        folders.push((*name, manager.get_path(name)));
+    }
+    let file = match args.get_one::<String>("output") {
+        Some(file) => Path::new(file).to_owned(),
+        None => handle_result(workspace::default_file(&names)),
+    };
+    handle_result(workspace::write(&folders, &file));
+    println!("{}", file.to_string_lossy());
+    if args.get_flag("open") {
+        for name in names {
+            handle_result(manager.touch(name));
+        }
+        handle_result(
+            std::process::Command::new("code")
+                .arg(&file)
+                .status()
+                .map_err(|e| format!("Couldn't run code: {}", e)),
+        );
+    }
+}
+
+/// Whether `project` passes `--completed` and `--modified-since` of `args`.
+fn lifecycle_matches(project: &Project, args: &ArgMatches) -> bool {
+    let completed = match args.get_one::<String>("completed").map(|c| c.as_str()) {
+        Some("yes") => project.get_completed().is_some(),
+        Some(_) => project.get_completed().is_none(),
+        None => true,
+    };
+    let modified = match args.get_one::<Duration>("modified-since") {
+        Some(since) => project.get_modified() >= OffsetDateTime::now_utc() - *since,
+        None => true,
+    };
+    completed && modified
+}
+
+/// Keys given with `--sort`, or `default` when it's missing.
+fn sort_keys(args: &ArgMatches, default: SortOrder) -> Vec<SortOrder> {
+    args.get_many::<SortOrder>("sort")
+        .map(|keys| keys.copied().collect())
+        .unwrap_or(vec![default])
 }
 
-fn search(mut manager: ProjectManager, default_executor: String, args: &ArgMatches) {
+/// Asks for one of the tags of `projects`, most used first.
+fn choose_tag(projects: &[Project]) -> Option<String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for tag in projects.iter().flat_map(|p| p.get_tags()) {
+        *counts.entry(tag).or_insert(0) += 1;
+    }
+    if counts.is_empty() {
+        return handle_result(Err("No project has tags"));
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let options: Vec<String> = counts
+        .iter()
+        .map(|(tag, count)| format!("{} ({})", tag, count))
+        .collect();
+    let chosen = Select::new("Choose a tag:", options.clone())
+        .prompt_skippable()
+        .answer()?;
+    let index = options.iter().position(|o| *o == chosen).unwrap();
+    Some(counts.swap_remove(index).0)
+}
+
+fn search(
+    mut manager: ProjectManager,
+    conf: &Config,
+    style: &TimeStyle,
+    hooks: &Hooks,
+    rules: &TagRules,
+    args: &ArgMatches,
+) {
     let order = match true {
-        true if args.get_flag("created") => SortOrder::Creation,
-        true if args.get_flag("name") => SortOrder::Name,
-        _ => SortOrder::AccessTime,
+        true if args.get_flag("created") => vec![SortOrder::Creation],
+        true if args.get_flag("name") => vec![SortOrder::Name],
+        _ => sort_keys(args, SortOrder::AccessTime),
     };
-    let mut projects = manager.get_projects(order);
+    let mut projects: Vec<Project> = manager.iter_projects_by(&order).cloned().collect();
+    // None(no priority or no flag) is less than any Some, so a missing flag keeps all
+    projects.retain(|p| p.get_priority() >= args.get_one::<u8>("min-priority").copied());
+    projects.retain(|p| args.get_flag("all") || !p.is_hidden());
+    projects.retain(|p| lifecycle_matches(p, args));
     if args.get_flag("invert") {
         projects.reverse();
     }
+    // inquire can't start with a filter, so the list is narrowed like its
+    // filter would, and typing narrows it further
+    let query = args.get_one::<String>("query");
+    if let Some(query) = query {
+        let lowercase = query.to_lowercase();
+        projects.retain(|p| {
+            ProjectEntry::new(p.clone(), style)
+                .to_string()
+                .to_lowercase()
+                .contains(&lowercase)
+        });
+        if projects.is_empty() {
+            return handle_result(Err(format!("No project matches '{}'", query)));
+        }
+    }
+    let tag = match args.get_flag("by-tag") {
+        true => match choose_tag(&projects) {
+            Some(tag) => {
+                projects.retain(|p| p.get_tags().contains(&tag));
+                Some(tag)
+            }
+            None => return,
+        },
+        false => None,
+    };
     // TODO : Handle case of no projects which results in inquire panicking
-    let res = Select::new("Choose a project:", projects)
-        .prompt_skippable()
-        .unwrap();
+    let entries = match args.get_one::<String>("group-by").map(|g| g.as_str()) {
+        Some("tag") => {
+            let mut groups = format::group_by_tag(projects);
+            if let Some((tag, _)) = groups.last_mut().filter(|(tag, _)| tag.is_empty()) {
+                *tag = "untagged".to_owned();
+            }
+            let width = groups
+                .iter()
+                .map(|(tag, _)| format::display_width(tag))
+                .max()
+                .unwrap_or(0);
+            groups
+                .into_iter()
+                .flat_map(|(tag, projects)| {
+                    projects.into_iter().enumerate().map(move |(i, p)| {
+                        let label = if i == 0 { tag.as_str() } else { "" };
+                        ProjectEntry::new(p, style).grouped(format::pad(label, width))
+                    })
+                })
+                .collect()
+        }
+        _ => projects
+            .into_iter()
+            .map(|p| ProjectEntry::new(p, style))
+            .collect(),
+    };
+    let filters: Vec<&str> = query.into_iter().chain(&tag).map(|f| f.as_str()).collect();
+    let message = match filters.is_empty() {
+        true => "Choose a project:".to_owned(),
+        false => format!("Choose a project({}):", filters.join(", ")),
+    };
+    if args.get_flag("many") {
+        let chosen = MultiSelect::new(&message, entries)
+            .prompt_skippable()
+            .answer()
+            .unwrap_or_default();
+        if chosen.is_empty() {
+            return;
+        }
+        // programs of tags every chosen project has still apply
+        let tags = chosen
+            .iter()
+            .map(|e| e.project.get_tags())
+            .reduce(|a, b| a.intersection(&b).cloned().collect())
+            .unwrap_or_default();
+        let names: Vec<String> = chosen
+            .iter()
+            .map(|e| e.project.get_name().to_owned())
+            .collect();
+        let cmd = args.get_one::<String>("execute").unwrap();
+        return handle_result(manager.exec_many(&names, executor::program(conf, &tags), cmd));
+    }
+    let res = Select::new(&message, entries).prompt_skippable().answer();
     if res.is_none() {
         return;
     }
-    let res = res.unwrap();
+    let res = res.unwrap().project;
     match true {
         true if args.get_flag("rename") => {
-            let temp = Text::new("New name:").prompt_skippable().unwrap();
+            let temp = Text::new("New name:").prompt_skippable().answer();
             if let Some(name) = temp {
-                handle_result(manager.rename(res.get_name(), &name))
+                rename_resolving(&mut manager, res.get_name(), &name, false)
+            }
+        }
+        true if args.get_flag("copy") => copy_path(&manager, res.get_name()),
+        true if args.get_flag("delete") => {
+            let path = manager.get_path(res.get_name());
+            let question = match git::status(&path) {
+                status @ (GitStatus::Uncommitted | GitStatus::Unpushed) => format!(
+                    "{} has {} changes, move it to the trash anyway?",
+                    res.get_name(),
+                    status
+                ),
+                _ => format!("Move {} to the trash?", res.get_name()),
+            };
+            let confirmed = Confirm::new(&question)
+                .with_default(false)
+                .prompt_skippable()
+                .answer();
+            if confirmed == Some(true) {
+                trash_project(&mut manager, hooks, res.get_name());
             }
         }
         true if args.get_flag("modify") => {
-            let name = res.get_name();
-            let mut tags = res.get_tags();
-            choose_tags(&mut manager, &mut tags);
-            handle_result(manager.modify(name, tags))
+            modify_tags(&mut manager, rules, res.get_name(), res.get_tags())
         }
         // default to exec
-        _ => handle_result(manager.exec(
-            res.get_name(),
-            default_executor,
-            args.get_one::<String>("execute").unwrap(),
+        _ => {
+            let worktree = choose_worktree(&res);
+            exec_project(
+                manager,
+                res.get_name(),
+                worktree.as_deref(),
+                None,
+                conf,
+                args.get_one::<String>("execute").unwrap(),
+            )
+        }
+    }
+}
+
+/// Asks which worktree of `project` to use if it has any which still exist.
+/// None is the main checkout.
+fn choose_worktree(project: &Project) -> Option<String> {
+    let branches: Vec<&String> = project
+        .get_worktrees()
+        .iter()
+        .filter(|(_, path)| path.is_dir())
+        .map(|(branch, _)| branch)
+        .collect();
+    if branches.is_empty() {
+        return None;
+    }
+    let main = "main checkout".to_owned();
+    let choice = Select::new(
+        "Choose a worktree:",
+        std::iter::once(&main).chain(branches).collect(),
+    )
+    .prompt_skippable()
+    .answer()?;
+    (choice != &main).then(|| choice.to_owned())
+}
+
+fn worktree(mut manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    match args.subcommand() {
+        Some(("add", args)) => {
+            let name = project_name(&manager, args, "project-name");
+            // with --id the only argument given is the branch
+            let branch = args
+                .get_one::<String>("branch")
+                .or(args.get_one::<String>("project-name"));
+            let branch = handle_result(branch.ok_or("Missing the branch"));
+            let path = conf.worktree_path(&name, branch);
+            handle_result(manager.add_worktree(&name, branch, path.clone()));
+            history::record("worktree", &name, "", branch);
+            println!("{}: {} checked out at {:?}", name, branch, path);
+        }
+        Some(("list", args)) => {
+            let name = project_name(&manager, args, "project-name");
+            let project = manager.get_project(&name).unwrap();
+            for (branch, path) in project.get_worktrees() {
+                let missing = if path.is_dir() { "" } else { "  (missing)" };
+                println!("{}  {:?}{}", branch, path, missing);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Copies path of project `name` to the clipboard.
+fn copy_path(manager: &ProjectManager, name: &str) {
+    let path = manager.get_path(name);
+    handle_result(clipboard::copy(&path.to_string_lossy()));
+    println!("Copied {}", path.to_string_lossy());
+}
+
+/// Prints every value of the metadata of a project which is set.
+fn show(manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    let project = manager.get_project(&name).unwrap();
+    let path = manager.get_path(&name);
+    if args.get_flag("json") {
+        let mut value = serde_json::to_value(project).unwrap();
+        value["path"] = serde_json::Value::from(path.to_string_lossy());
+        println!("{}", value);
+        return;
+    }
+    println!("name: {}", name);
+    if let Some(id) = project.get_id() {
+        println!("id: {}", id);
+    }
+    if let Some(title) = project.get_title() {
+        println!("title: {}", title);
+    }
+    println!("path: {}", path.display());
+    println!("tags: {}", history::tags(&project.get_tags()));
+    println!("created: {}", style.display(project.get_created()));
+    println!("accessed: {}", style.display(project.get_accessed()));
+    println!("modified: {}", style.display(project.get_modified()));
+    if let Some(completed) = project.get_completed() {
+        println!("completed: {}", format::format_date(completed));
+    }
+    if let Some(due) = project.get_due() {
+        println!(
+            "{}",
+            format::due_text(due, style.today(), format::use_color())
+        );
+    }
+    if let Some(priority) = project.get_priority() {
+        println!("priority: {}", priority);
+    }
+    if let Some(owner) = project.get_owner() {
+        println!("owner: {}", owner);
+    }
+    let flags: Vec<&str> = [
+        (project.is_locked(), "locked"),
+        (project.is_hidden(), "hidden"),
+        (project.is_sealed(), "encrypted"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| set.then_some(flag))
+    .collect();
+    if !flags.is_empty() {
+        println!("flags: {}", flags.join(", "));
+    }
+    if let Some(cmd) = project.get_last_command() {
+        println!("last command: {}", cmd);
+    }
+    for (branch, dir) in project.get_worktrees() {
+        println!("worktree {}: {}", branch, dir.display());
+    }
+    for dir in project.get_subprojects() {
+        println!("subproject: {}", dir.display());
+    }
+    for key in project.get_all_custom().keys() {
+        let value = Field::Custom(key.to_owned()).value(project, &path);
+        println!("{}: {}", key, value);
+    }
+}
+
+fn path(manager: ProjectManager, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    if args.get_flag("copy") {
+        copy_path(&manager, &name);
+    } else {
+        println!("{}", manager.get_path(&name).to_string_lossy());
+    }
+}
+
+fn exists(manager: ProjectManager, args: &ArgMatches) {
+    let name = args.get_one::<String>("project-name").unwrap();
+    let exists = manager.get_project(name).is_some()
+        && (!args.get_flag("path") || manager.get_path(name).is_dir());
+    if !exists {
+        exit(1);
+    }
+}
+
+/// Opens metadata of the project in `editor` until it's valid or the user
+/// gives up. The file is edited as a copy so it's never left broken.
+fn edit(mut manager: ProjectManager, editor: &str, rules: &TagRules, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    handle_result(manager.check_unlocked(&name));
+    let old = manager.get_project(&name).unwrap().clone();
+    // created exclusively and only readable by the user, so nobody else can
+    // point it elsewhere or swap its content
+    let file = handle_result(
+        tempfile::Builder::new()
+            .prefix("pj-")
+            .suffix(".json")
+            .tempfile()
+            .map_err(|e| format!("Couldn't create a temporary file: {}", e)),
+    )
+    .into_temp_path();
+    let res = edit_file(&file, &old, editor, rules);
+    let _ = file.close();
+    let Some(project) = handle_result(res) else {
+        println!("Discarded changes");
+        return;
+    };
+    handle_result(manager.replace(&name, project));
+    let new = manager.get_project(&name).unwrap().get_tags();
+    history::record_tags("edit", &name, &old.get_tags(), &new);
+}
+
+/// Lets the user edit `project` as JSON in `file` until it parses or they
+/// give up. Returns errors instead of exiting so the caller can remove
+/// `file` first.
+fn edit_file(
+    file: &Path,
+    project: &Project,
+    editor: &str,
+    rules: &TagRules,
+) -> Result<Option<Project>, String> {
+    std::fs::write(file, serde_json::to_string_pretty(project).unwrap())
+        .map_err(|e| format!("Couldn't write {:?}: {}", file, e))?;
+    let editor: Vec<&str> = editor.split(' ').collect();
+    loop {
+        let status = std::process::Command::new(editor[0])
+            .args(&editor[1..])
+            .arg(file)
+            .status()
+            .map_err(|e| format!("Couldn't run {}: {}", editor[0], e))?;
+        let res = match status.success() {
+            true => std::fs::read_to_string(file).map_err(|e| e.to_string()),
+            false => Err(format!("{} failed", editor[0])),
+        }
+        .and_then(|data| project::parse_metadata(&data))
+        .and_then(|p| {
+            p.get_tags().iter().try_for_each(|t| rules.check(t))?;
+            Ok(p)
+        });
+        match res {
+            Ok(project) => return Ok(Some(project)),
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                let again = match Confirm::new("Edit again?")
+                    .with_default(true)
+                    .prompt_skippable()
+                {
+                    Ok(again) => again,
+                    Err(InquireError::OperationInterrupted) => None,
+                    Err(e) => return Err(format!("Couldn't prompt: {}", e)),
+                };
+                if again != Some(true) {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+fn list(manager: ProjectManager, warnings: &[String], args: &ArgMatches) {
+    let fields: Vec<&Field> = args.get_many::<Field>("fields").unwrap().collect();
+    let min_priority = args.get_one::<u8>("min-priority").copied();
+    let owner = args.get_one::<String>("owner");
+    let mut projects = Vec::new();
+    for project in manager
+        .iter_projects_by(&sort_keys(args, SortOrder::Name))
+        .filter(|p| p.get_priority() >= min_priority)
+        .filter(|p| owner.is_none() || p.get_owner() == owner)
+        .filter(|p| args.get_flag("all") || !p.is_hidden())
+        .filter(|p| lifecycle_matches(p, args))
+    {
+        let path = manager.get_path(project.get_name());
+        let values: Vec<String> = fields.iter().map(|f| f.value(project, &path)).collect();
+        if args.get_flag("json") {
+            projects.push(
+                fields
+                    .iter()
+                    .map(|f| f.key())
+                    .zip(values.into_iter().map(serde_json::Value::from))
+                    .collect::<serde_json::Map<_, _>>(),
+            );
+        } else {
+            println!("{}", values.join("\t"));
+        }
+    }
+    if args.get_flag("json") {
+        println!(
+            "{}",
+            serde_json::json!({ "projects": projects, "warnings": warnings })
+        );
+    }
+}
+
+/// Prints matching projects like grep, exiting with 1 if there are none.
+fn grep(manager: ProjectManager, args: &ArgMatches) {
+    let pattern = args.get_one::<String>("pattern").unwrap();
+    let pattern = match args.get_flag("regex") {
+        true => Regex::new(pattern),
+        false => Regex::new(&format!("(?i){}", regex::escape(pattern))),
+    };
+    let pattern = handle_result(pattern.map_err(|e| format!("Invalid pattern: {}", e)));
+    let tags: Vec<String> = args
+        .get_many::<String>("tag")
+        .map(|tags| tags.map(|t| manager.normalize_tag(t)).collect())
+        .unwrap_or_default();
+    let mut found = false;
+    for project in manager
+        .iter_projects_by(&sort_keys(args, SortOrder::Name))
+        .filter(|p| p.matches(&pattern))
+        .filter(|p| tags.iter().all(|t| p.get_tags().contains(t)))
+    {
+        found = true;
+        println!("{}", project.get_name());
+    }
+    if !found {
+        exit(1);
+    }
+}
+
+fn due(manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let today = style.today();
+    let until = today + Duration::days(*args.get_one::<i64>("days").unwrap());
+    let mut projects: Vec<(Date, &Project)> = manager
+        .iter_projects(SortOrder::Name)
+        .filter_map(|p| p.get_due().map(|due| (due, p)))
+        .filter(|(due, _)| *due <= until)
+        .collect();
+    projects.sort_by_key(|(due, _)| *due);
+    if projects.is_empty() {
+        println!("Nothing is due");
+    }
+    for (due, project) in projects {
+        println!(
+            "{}  {}",
+            format::due_text(due, today, format::use_color()),
+            project.get_name()
+        );
+    }
+}
+
+/// Refuses to go on when the git repository at `path` has work which would
+/// be lost, unless `force` is set.
+fn check_git(path: &Path, force: bool) -> Result<(), String> {
+    match git::status(path) {
+        status @ (GitStatus::Uncommitted | GitStatus::Unpushed) if !force => Err(format!(
+            "{:?} has {} changes, use --force to continue anyway",
+            path, status
         )),
+        _ => Ok(()),
+    }
+}
+
+fn archive(mut manager: ProjectManager, args: &ArgMatches) {
+    if args.get_one::<String>("project-name").is_none() && !args.contains_id("id") {
+        for project in manager.archived() {
+            println!(
+                "{}  {}",
+                project.get_name(),
+                project.get_archived().unwrap_or(Archived::Moved)
+            );
+        }
+        return;
+    }
+    let name = project_name(&manager, args, "project-name");
+    handle_result(check_git(&manager.get_path(&name), args.get_flag("force")));
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    let compress = args.get_flag("compress");
+    handle_result(manager.archive(&name, compress));
+    let state = if compress {
+        Archived::Compressed
+    } else {
+        Archived::Moved
+    };
+    history::record("archive", &name, "", &state.to_string());
+    println!("{}: {}", name, state);
+}
+
+/// Name given as argument `project-name` of projects which aren't loaded,
+/// like archived or deleted ones, which can't be found by `--id`.
+fn unloaded_name(args: &ArgMatches) -> &String {
+    let name = args.get_one::<String>("project-name");
+    handle_result(name.ok_or("--id only finds projects which aren't archived or deleted"))
+}
+
+fn restore(mut manager: ProjectManager, args: &ArgMatches) {
+    let name = unloaded_name(args);
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    handle_result(manager.restore(name));
+    history::record("restore", name, "", "");
+    println!("{}: restored to {:?}", name, manager.get_path(name));
+}
+
+fn touch(mut manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    let time = match args.get_one::<String>("at") {
+        Some(at) => handle_result(format::parse_datetime(at, style.offset())),
+        None => OffsetDateTime::now_utc(),
+    };
+    let old = manager.get_project(&name).unwrap().get_accessed();
+    handle_result(manager.set_accessed(&name, time));
+    history::record("touch", &name, &style.absolute(old), &style.absolute(time));
+    println!("{}: accessed {}", name, style.display(time));
+}
+
+fn delete(mut manager: ProjectManager, hooks: &Hooks, args: &ArgMatches) {
+    let name = project_name(&manager, args, "project-name");
+    handle_result(check_git(&manager.get_path(&name), args.get_flag("force")));
+    if args.get_flag("unlock") {
+        manager.unlock();
+    }
+    trash_project(&mut manager, hooks, &name);
+}
+
+/// Moves project `name` to the trash unless a script vetoes it.
+fn trash_project(manager: &mut ProjectManager, hooks: &Hooks, name: &str) {
+    let path = manager.get_path(name);
+    let project = manager.get_project(name).unwrap();
+    if let Some(reason) = handle_result(hooks.veto_delete(project, &path)) {
+        handle_result::<(), _>(Err(format!("{} wasn't deleted: {}", name, reason)));
+    }
+    handle_result(manager.delete(name));
+    history::record("delete", name, &path.to_string_lossy(), "");
+    println!("{}: moved to trash", name);
+}
+
+/// Moves `from` to `to`, copying it when they're on different filesystems.
+/// Fails, leaving `from` in place, if the files and sizes of `to` don't match.
+fn move_checked(from: &Path, to: &Path) -> Result<(), String> {
+    let (size, metadata) = (
+        disk::dir_size(from),
+        Disk.read(&from.join(project::PROJECT_FILE)).ok(),
+    );
+    let copied = Disk.rename(from, to).is_err();
+    if copied {
+        Disk.copy_dir(from, to)?;
+    }
+    if disk::dir_size(to) != size || Disk.read(&to.join(project::PROJECT_FILE)).ok() != metadata {
+        return Err(format!("{:?} doesn't match {:?} after moving it", to, from));
+    }
+    match copied {
+        true => Disk.remove_dir(from),
+        false => Ok(()),
+    }
+}
+
+/// Moves the projects in root, the archive and the trash into another
+/// directory and points the config at it. If root has no projects but the
+/// other directory has, they were moved by hand and only the config changes.
+fn migrate_root(manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    let old = manager.get_root().to_owned();
+    let new = Path::new(args.get_one::<String>("new-dir").unwrap());
+    let new = handle_result(std::env::current_dir().map_err(|e| e.to_string())).join(new);
+    let old_canonical = old.canonicalize().unwrap_or(old.clone());
+    if new.canonicalize().is_ok_and(|new| new == old_canonical) {
+        return handle_result(Err(format!("{:?} is already the root", new)));
+    }
+    if new.starts_with(&old) || new.starts_with(&old_canonical) {
+        return handle_result(Err(format!("{:?} is inside the root", new)));
+    }
+    let mut dirs: Vec<PathBuf> = manager
+        .iter_projects(SortOrder::Name)
+        .map(|p| manager.get_path(p.get_name()))
+        .filter(|dir| dir.parent() == Some(old.as_path()))
+        .collect();
+    dirs.extend(
+        [ARCHIVE_DIR, trash::TRASH_DIR]
+            .iter()
+            .map(|d| old.join(d))
+            .filter(|d| d.is_dir()),
+    );
+    if dirs.is_empty() {
+        let projects = Disk
+            .list_dirs(&new)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|d| d.join(project::PROJECT_FILE).is_file())
+            .count();
+        if projects == 0 {
+            return handle_result(Err(format!("No projects in {:?} or {:?}", old, new)));
+        }
+        handle_result(conf.set("dir", new.to_string_lossy().into()));
+        handle_result(index::move_root(&old, &new));
+        handle_result(Registry::load().move_root(&old, &new));
+        handle_result(trash::move_root(&Disk, &old, &new));
+        println!(
+            "Found {} project(s) already in {:?}, root is now {:?}",
+            projects, new, new
+        );
+        return;
+    }
+    let clashes: Vec<String> = dirs
+        .iter()
+        .map(|d| new.join(d.file_name().unwrap()))
+        .filter(|d| d.exists())
+        .map(|d| format!("  {:?} already exists", d))
+        .collect();
+    if !clashes.is_empty() {
+        return handle_result(Err(format!("Nothing was moved:\n{}", clashes.join("\n"))));
+    }
+    if !args.get_flag("yes") {
+        let confirmed = Confirm::new(&format!(
+            "Move {} director(y/ies) from {:?} to {:?}?",
+            dirs.len(),
+            old,
+            new
+        ))
+        .with_default(false)
+        .prompt_skippable()
+        .answer();
+        if confirmed != Some(true) {
+            return;
+        }
+    }
+    handle_result(Disk.create_dir_all(&new));
+    for dir in &dirs {
+        let name = dir.file_name().unwrap();
+        // the config still points at the old root, so a failure leaves a
+        // working state and moving again continues where it stopped
+        handle_result(move_checked(dir, &new.join(name)));
+        println!("moved {}", name.to_string_lossy());
+    }
+    handle_result(conf.set("dir", new.to_string_lossy().into()));
+    handle_result(index::move_root(&old, &new));
+    handle_result(Registry::load().move_root(&old, &new));
+    handle_result(trash::move_root(&Disk, &old, &new));
+    println!("Root is now {:?}", new);
+    if manager
+        .iter_projects(SortOrder::Name)
+        .any(|p| !p.get_worktrees().is_empty())
+    {
+        error::warn("git worktrees of moved projects may need `git worktree repair`");
+    }
+}
+
+fn backup(manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    match args.subcommand() {
+        Some(("list", _)) => {
+            for timestamp in handle_result(backup::list()) {
+                println!("{}", timestamp);
+            }
+        }
+        Some(("restore", args)) => {
+            let timestamp = args.get_one::<String>("timestamp").unwrap();
+            // restoring can be undone by restoring this one
+            let current = handle_result(backup::create(
+                &manager.metadata_files(),
+                conf.backup_keep.max(1) + 1,
+            ));
+            let (restored, missing) = handle_result(backup::restore(timestamp));
+            for dir in missing {
+                error::warn(format!("{:?} doesn't exist anymore, skipped", dir));
+            }
+            println!(
+                "Restored {} project(s) from {}, the metadata before was saved as {}",
+                restored, timestamp, current
+            );
+        }
+        _ => {
+            let metadata = manager.metadata_files();
+            let timestamp = handle_result(backup::create(&metadata, conf.backup_keep));
+            println!("Saved {} project(s) as {}", metadata.len(), timestamp);
+        }
+    }
+}
+
+fn trash(mut manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    match args.subcommand() {
+        Some(("list", _)) => {
+            for entry in handle_result(manager.trashed()) {
+                println!(
+                    "{}  deleted {}  from {:?}",
+                    entry.name,
+                    style.display(entry.deleted),
+                    entry.path
+                );
+            }
+        }
+        Some(("restore", args)) => {
+            let name = unloaded_name(args);
+            handle_result(manager.restore_trashed(name));
+            history::record(
+                "restore",
+                name,
+                "",
+                &manager.get_path(name).to_string_lossy(),
+            );
+            println!("{}: restored to {:?}", name, manager.get_path(name));
+        }
+        Some(("empty", _)) => {
+            let removed = handle_result(manager.empty_trash(None));
+            println!("Removed {} project(s) from trash", removed.len());
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn du(manager: ProjectManager, args: &ArgMatches) {
+    let mut cache = SizeCache::load();
+    let refresh = args.get_flag("refresh");
+    let progress = Progress::new(args.get_flag("quiet"));
+    let mut sizes: Vec<(String, u64)> = manager
+        .iter_projects(SortOrder::Name)
+        .enumerate()
+        .map(|(i, p)| {
+            progress.update(i, manager.len(), p.get_name());
+            let size = cache.get(&manager.get_path(p.get_name()), refresh);
+            (p.get_name().to_owned(), size)
+        })
+        .collect();
+    drop(progress);
+    handle_result(cache.save());
+    if !args.get_flag("name") {
+        sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    }
+    if args.get_flag("invert") {
+        sizes.reverse();
+    }
+    let total: u64 = sizes.iter().map(|(_, size)| size).sum();
+    for (name, size) in sizes {
+        println!("{:>10}  {}", disk::format_size(size), name);
+    }
+    println!("{:>10}  total", disk::format_size(total));
+}
+
+fn clean(mut manager: ProjectManager, cleaners: &[Cleaner], args: &ArgMatches) {
+    let projects: Vec<Project> = match args.get_one::<String>("tag") {
+        Some(tag) => manager.with_tag(tag, SortOrder::Name),
+        None => {
+            let name = project_name(&manager, args, "project-name");
+            vec![handle_result(manager.get_mut_project(&name)).clone()]
+        }
+    };
+    let mut cache = SizeCache::load();
+    let mut reclaimed = 0;
+    let progress = Progress::new(args.get_flag("quiet"));
+    for (i, project) in projects.iter().enumerate() {
+        progress.update(i, projects.len(), project.get_name());
+        let path = manager.get_path(project.get_name());
+        let before = disk::dir_size(&path);
+        if !handle_result(disk::clean(&path, cleaners)) {
+            progress.println(format!("{}: nothing to clean", project.get_name()));
+            continue;
+        }
+        let after = disk::dir_size(&path);
+        cache.invalidate(&path);
+        reclaimed += before.saturating_sub(after);
+        progress.println(format!(
+            "{}: reclaimed {}",
+            project.get_name(),
+            disk::format_size(before.saturating_sub(after))
+        ));
+    }
+    drop(progress);
+    handle_result(cache.save());
+    println!("total reclaimed: {}", disk::format_size(reclaimed));
+}
+
+fn report(manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let order = match true {
+        true if args.get_flag("accessed") => vec![SortOrder::AccessTime],
+        _ => sort_keys(args, SortOrder::Name),
+    };
+    let mut projects: Vec<&Project> = manager.iter_projects_by(&order).collect();
+    if args.get_flag("invert") {
+        projects.reverse();
+    }
+    let columns: Vec<&Column> = args.get_many::<Column>("columns").unwrap().collect();
+    let html = args.get_one::<PathBuf>("html");
+    // relative times can't be sorted in the page
+    let style = &match html {
+        Some(_) => style.clone().with_relative(false),
+        None => style.clone(),
+    };
+    let mut cache = SizeCache::load();
+    let mut table = Table::new(columns.iter().map(|c| c.header().to_owned()).collect());
+    let progress = Progress::new(args.get_flag("quiet"));
+    for (i, project) in projects.iter().enumerate() {
+        progress.update(i, projects.len(), project.get_name());
+        let path = manager.get_path(project.get_name());
+        table.push(
+            columns
+                .iter()
+                .map(|c| c.cell(project, &path, &mut cache, style))
+                .collect(),
+        );
+    }
+    drop(progress);
+    handle_result(cache.save());
+    if let Some(file) = html {
+        let page = dashboard::render(&table, &projects, &manager.tag_counts(), style.offset());
+        handle_result(
+            std::fs::write(file, page).map_err(|e| format!("Couldn't write {:?}: {}", file, e)),
+        );
+        return;
+    }
+    print!(
+        "{}",
+        table.render(args.get_one::<OutputFormat>("format").unwrap())
+    );
+}
+
+fn export(manager: ProjectManager, args: &ArgMatches) {
+    let (dir, links) = match args.get_one::<PathBuf>("obsidian") {
+        Some(dir) => (dir, export::Links::Wiki),
+        None => (
+            args.get_one::<PathBuf>("markdown").unwrap(),
+            export::Links::Markdown,
+        ),
+    };
+    let projects: Vec<(&Project, PathBuf)> = manager
+        .iter_projects(SortOrder::Name)
+        .map(|p| (p, manager.get_path(p.get_name())))
+        .collect();
+    let removed = handle_result(export::write(dir, &projects, links));
+    println!(
+        "Exported {} project(s) to {:?}, removed {} old note(s)",
+        projects.len(),
+        dir,
+        removed
+    );
+}
+
+fn show_stats(manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let projects: Vec<&Project> = manager.iter_projects(SortOrder::AccessTime).collect();
+    if args.get_flag("heatmap") {
+        let activity = stats::activity(projects.iter().copied(), style.offset());
+        print!(
+            "{}",
+            stats::heatmap(
+                &activity,
+                *args.get_one::<u16>("weeks").unwrap(),
+                style.offset()
+            )
+        );
+        return;
+    }
+    println!("projects: {}", projects.len());
+    println!("tags: {}", manager.tags().len());
+    if let Some(project) = projects.first() {
+        println!(
+            "last accessed: {} ({})",
+            project.get_name(),
+            style.display(project.get_accessed())
+        );
+    }
+}
+
+fn prompt_segment(conf: &Config, args: &ArgMatches) {
+    let cwd = match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(_) => return,
+    };
+    if let Some((_, project)) = project::find_containing(&Disk, Path::new(&conf.dir), &cwd) {
+        let mut tags: Vec<String> = project.get_tags().into_iter().collect();
+        tags.sort();
+        let format = args.get_one::<String>("format").unwrap();
+        let segment = match tags.first() {
+            Some(tag) => format.replace("{tag}", tag),
+            // drop whatever surrounds {tag} like the parentheses in default format
+            None => format
+                .split_whitespace()
+                .filter(|part| !part.contains("{tag}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        println!("{}", segment.replace("{name}", project.get_name()));
     }
 }
 
-pub fn handle(conf: Config, matches: ArgMatches) {
-    let manager = ProjectManager::load(Path::new(&conf.dir).to_owned());
+fn shell_init(args: &ArgMatches) {
+    let exe = std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "pj".to_owned());
+    let exe = format!("'{}'", exe.replace('\'', "'\\''"));
+    let shell = args.get_one::<String>("shell").unwrap().as_str();
+    match shell {
+        "fish" => println!(
+            "function pjcd; set -l dir ({} path $argv[1]); and cd $dir; end",
+            exe
+        ),
+        _ => println!(
+            "pjcd() {{ local dir; dir=\"$({} path \"$1\")\" && cd \"$dir\"; }}",
+            exe
+        ),
+    }
+    if !args.get_flag("hook") {
+        return;
+    }
+    match shell {
+        "bash" => println!(
+            "__pj_hook() {{ if [ \"$PWD\" != \"$__pj_dir\" ]; then __pj_dir=\"$PWD\"; {} touch-if-project \"$PWD\" 2>/dev/null; fi; }}\n\
+            PROMPT_COMMAND=\"__pj_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\"",
+            exe
+        ),
+        "zsh" => println!(
+            "__pj_hook() {{ {} touch-if-project \"$PWD\" 2>/dev/null; }}\n\
+            autoload -Uz add-zsh-hook && add-zsh-hook chpwd __pj_hook",
+            exe
+        ),
+        _ => println!(
+            "function __pj_hook --on-variable PWD; {} touch-if-project \"$PWD\" 2>/dev/null; end",
+            exe
+        ),
+    }
+}
+
+/// Runs on every `cd` from shell hooks, so it only reads the index and the
+/// metadata of one project and never fails loudly.
+fn touch_if_project(conf: &Config, args: &ArgMatches) {
+    let dir = match args.get_one::<PathBuf>("dir") {
+        Some(dir) => dir.to_owned(),
+        None => match std::env::current_dir() {
+            Ok(cwd) => cwd,
+            Err(_) => return,
+        },
+    };
+    let dir = dir.canonicalize().unwrap_or(dir);
+    if let Some((_, path)) = index::lookup(Path::new(&conf.dir), &dir) {
+        let _ = project::touch_dir(&Disk, &path);
+    }
+}
+
+fn current(manager: ProjectManager, args: &ArgMatches) {
+    let name = handle_result(manager.current());
+    if args.get_flag("path") {
+        println!("{}", manager.get_path(&name).to_string_lossy());
+    } else {
+        println!("{}", name);
+    }
+}
+
+fn show_history(style: &TimeStyle, args: &ArgMatches) {
+    let project = args.get_one::<String>("project-name").map(|p| p.as_str());
+    for entry in history::load(project) {
+        let change = match (entry.command.as_str(), entry.diff) {
+            (_, Some(diff)) => diff.to_string(),
+            ("create" | "init", None) => format!("tags: {}", entry.new),
+            _ => format!("{} -> {}", entry.old, entry.new),
+        };
+        println!(
+            "{}  {:<7} {}  {}",
+            style.display(entry.time),
+            entry.command,
+            entry.project,
+            change
+        );
+    }
+}
+
+fn summary(manager: ProjectManager, style: &TimeStyle, args: &ArgMatches) {
+    let since = OffsetDateTime::now_utc() - *args.get_one::<Duration>("since").unwrap();
+    let date = format::format_date(since.to_offset(style.offset()).date());
+    let summary = stats::summary(
+        manager.iter_projects(SortOrder::Name),
+        &history::load(None),
+        since,
+    );
+    match summary.is_empty() {
+        true => println!("No project was touched since {}", date),
+        false => print!("Since {}:\n\n{}", date, summary),
+    }
+}
+
+fn list_tags(manager: ProjectManager, args: &ArgMatches) {
+    let mut counts: Vec<(String, usize)> = manager.tag_counts().into_iter().collect();
+    if args.get_flag("counts") {
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (tag, count) in counts {
+            println!("{:>5}  {}", count, tag);
+        }
+    } else {
+        counts.sort();
+        for (tag, _) in counts {
+            println!("{}", tag);
+        }
+    }
+}
+
+fn tag_cloud(manager: ProjectManager) {
+    let width = match format::interactive() {
+        true => crossterm::terminal::size().map_or(80, |(cols, _)| cols as usize),
+        false => 80,
+    };
+    print!(
+        "{}",
+        stats::tag_cloud(&manager.tag_counts(), width, format::use_color())
+    );
+}
+
+/// Prints the resolved config with where each value came from.
+fn diff_config(conf: &Config, matches: &ArgMatches) {
+    println!("config file: {}", Config::path().display());
+    if let Some(profile) = &conf.profile {
+        let from = match matches.value_source("profile") {
+            Some(ValueSource::EnvVariable) => "PM_PROFILE",
+            _ => "--profile",
+        };
+        println!("profile: {} (from {})", profile, from);
+    }
+    let mut sources = conf.sources();
+    if matches.get_flag("absolute") {
+        if let Some(entry) = sources
+            .iter_mut()
+            .find(|(key, _, _)| key == "relative_time")
+        {
+            entry.1 = false.into();
+        }
+    }
+    let width = sources
+        .iter()
+        .map(|(key, _, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    for (key, value, source) in sources {
+        let source = match key.as_str() {
+            "relative_time" if matches.get_flag("absolute") => "--absolute".to_owned(),
+            _ => source.to_string(),
+        };
+        println!("{:width$}  {}  ({})", key, value, source);
+    }
+}
+
+/// Removes what the stores still remember about directories which were
+/// deleted by hand and prints it.
+fn gc(conf: &Config) {
+    let root = Path::new(&conf.dir);
+    let mut removed: Vec<(&str, String)> = Vec::new();
+    let show = |p: PathBuf| p.to_string_lossy().to_string();
+    removed.extend(
+        handle_result(index::prune())
+            .into_iter()
+            .map(|p| ("index", show(p))),
+    );
+    removed.extend(
+        handle_result(trash::prune(&Disk, root))
+            .into_iter()
+            .map(|e| ("trash", format!("{} (from {})", e.name, e.path.display()))),
+    );
+    removed.extend(
+        handle_result(Registry::load().prune())
+            .into_iter()
+            .map(|p| ("external projects", show(p))),
+    );
+    if removed.is_empty() {
+        println!("Nothing to clean");
+        return;
+    }
+    for (store, entry) in &removed {
+        println!("{}: {}", store, entry);
+    }
+    println!("Removed {} stale entries", removed.len());
+}
+
+/// Prints problems found while loading projects, like broken metadata or
+/// names colliding on case-insensitive filesystems, and a root which is a
+/// git repository. Exits with 1 if there are any.
+fn doctor(root: &Path, warnings: &[String]) {
+    let mut warnings = warnings.to_vec();
+    if root.join(".git").exists() {
+        warnings.push(format!(
+            "root {:?} is a git repository, so it tracks the files of every project in it",
+            root
+        ));
+    }
+    if warnings.is_empty() {
+        println!("No problems found");
+        return;
+    }
+    for warning in &warnings {
+        println!("- {}", warning);
+    }
+    println!("{} problem(s) found", warnings.len());
+    exit(1);
+}
+
+/// Saves projects whose tags had to be normalized, `changed` being the ones
+/// already normalized when loading.
+fn normalize_tags(mut manager: ProjectManager, mut changed: Vec<(String, HashSet<String>)>) {
+    changed.extend(manager.normalize_tags());
+    if changed.is_empty() {
+        println!("All tags are already normalized");
+    }
+    let changes = changed
+        .iter()
+        .map(|(name, _)| (name.clone(), manager.get_project(name).unwrap().get_tags()))
+        .collect();
+    handle_result(manager.modify_all(changes));
+    for (name, old) in changed {
+        let tags = manager.get_project(&name).unwrap().get_tags();
+        let diff = history::record_tags("normalize", &name, &old, &tags);
+        println!("{}: {}", name, diff);
+    }
+}
+
+/// Replaces a tag in every project using it and in the config.
+fn rename_tag(mut manager: ProjectManager, rules: &TagRules, args: &ArgMatches) {
+    let old = manager.normalize_tag(args.get_one::<String>("old").unwrap());
+    let new = manager.normalize_tag(args.get_one::<String>("new").unwrap());
+    handle_result(rules.check(&new));
+    if old == new {
+        return handle_result(Err("The new name of the tag is the same"));
+    }
+    let changes: Vec<(String, HashSet<String>)> = manager
+        .iter_projects(SortOrder::Name)
+        .filter(|p| p.get_tags().contains(&old))
+        .map(|p| (p.get_name().to_owned(), p.get_tags()))
+        .collect();
+    handle_result(
+        manager.modify_all(
+            changes
+                .iter()
+                .map(|(name, tags)| {
+                    let mut tags = tags.clone();
+                    tags.remove(&old);
+                    tags.insert(new.clone());
+                    (name.clone(), tags)
+                })
+                .collect(),
+        ),
+    );
+    for (name, tags) in &changes {
+        let new_tags = manager.get_project(name).unwrap().get_tags();
+        let diff = history::record_tags("rename-tag", name, tags, &new_tags);
+        println!("{}: {}", name, diff);
+    }
+    let sections = handle_result(Config::rename_tag(&old, &new));
+    for section in &sections {
+        println!("config: {}", section);
+    }
+    if changes.is_empty() && sections.is_empty() {
+        handle_result::<(), _>(Err(format!("No project or config uses tag '{}'", old)));
+    }
+}
+
+/// Lists projects missing tags implied by their other tags, adding them with
+/// `--fix`. Exits with 1 if some are missing and weren't fixed.
+fn check_tag_rules(mut manager: ProjectManager, rules: &TagRules, args: &ArgMatches) {
+    let missing: Vec<(String, HashSet<String>)> = manager
+        .iter_projects(SortOrder::Name)
+        .filter_map(|p| {
+            let tags = p.get_tags();
+            let missing: HashSet<String> =
+                rules.implied(&tags).difference(&tags).cloned().collect();
+            (!missing.is_empty()).then(|| (p.get_name().to_owned(), missing))
+        })
+        .collect();
+    if missing.is_empty() {
+        println!("All projects follow the tag rules");
+        return;
+    }
+    for (name, tags) in &missing {
+        println!("{}: missing {}", name, history::tags(tags));
+    }
+    if !args.get_flag("fix") {
+        exit(1);
+    }
+    let changes: Vec<(String, HashSet<String>, HashSet<String>)> = missing
+        .into_iter()
+        .map(|(name, tags)| {
+            let old = manager.get_project(&name).unwrap().get_tags();
+            let new = old.union(&tags).cloned().collect();
+            (name, old, new)
+        })
+        .collect();
+    handle_result(
+        manager.modify_all(
+            changes
+                .iter()
+                .map(|(name, _, new)| (name.clone(), new.clone()))
+                .collect(),
+        ),
+    );
+    for (name, old, new) in changes {
+        history::record_tags("modify", &name, &old, &new);
+    }
+}
+
+/// What's wrong with `name` according to `pattern` and `slugify`, if anything.
+fn name_problem(name: &str, pattern: Option<&Regex>, slugify: bool) -> Option<String> {
+    if let Some(pattern) = pattern.filter(|p| !p.is_match(name)) {
+        return Some(format!("doesn't match name_pattern '{}'", pattern));
+    }
+    let slug = project::slugify(name);
+    (slugify && slug != name).then(|| format!("isn't slugified(would be '{}')", slug))
+}
+
+/// Lists projects with names breaking the naming rules of the config and
+/// renames them to their slug with `--fix`, or to names asked for in a
+/// terminal. Exits with 1 if some weren't renamed.
+fn check_names(mut manager: ProjectManager, conf: &Config, args: &ArgMatches) {
+    let pattern = handle_result(conf.name_pattern());
+    let problems: Vec<(String, String)> = manager
+        .iter_projects(SortOrder::Name)
+        .filter(|p| manager.parent(p.get_name()).is_none())
+        .filter_map(|p| {
+            let problem = name_problem(p.get_name(), pattern.as_ref(), conf.slugify_names)?;
+            Some((p.get_name().to_owned(), problem))
+        })
+        .collect();
+    if problems.is_empty() {
+        println!("All project names follow the naming rules");
+        return;
+    }
+    let ask = !args.get_flag("fix") && std::io::stdin().is_terminal();
+    let mut left = 0;
+    for (name, problem) in problems {
+        println!("{}: {}", name, problem);
+        let slug = project::slugify(&name);
+        let new = match (args.get_flag("fix"), ask) {
+            (true, _) => Some(slug),
+            (false, true) => Text::new(&format!("New name for {}:", name))
+                .with_initial_value(&slug)
+                .with_help_message("empty or Esc to skip")
+                .prompt_skippable()
+                .answer()
+                .filter(|n| !n.is_empty()),
+            (false, false) => None,
+        };
+        let new = match new {
+            Some(new) => new,
+            None => {
+                left += 1;
+                continue;
+            }
+        };
+        if new == name {
+            error::warn(format!("{} has to be renamed by hand", name));
+            left += 1;
+            continue;
+        }
+        if let Some(problem) = name_problem(&new, pattern.as_ref(), conf.slugify_names) {
+            error::warn(format!(
+                "not renaming {} to {}, which {}",
+                name, new, problem
+            ));
+            left += 1;
+            continue;
+        }
+        let title = manager.get_project(&name).unwrap().get_title().cloned();
+        if let Err(e) = manager.rename(&name, &new) {
+            error::warn(format!("couldn't rename {}: {}", name, e));
+            left += 1;
+            continue;
+        }
+        history::record("rename", &name, &name, &new);
+        // like projects created with slugify_names, the old name stays readable
+        if title.is_none() && !manager.get_project(&new).unwrap().is_sealed() {
+            handle_result(manager.set_title(&new, Some(name.clone())));
+        }
+        println!("{}: renamed to {}", name, new);
+    }
+    if left > 0 {
+        exit(1);
+    }
+}
+
+pub fn handle_config(matches: &ArgMatches) {
+    if let Some(("schema", _)) = matches.subcommand() {
+        println!("{}", config::schema());
+    }
+}
+
+/// Runs the subcommand of `matches`. `timings` holds the phases timed before,
+/// like loading the config, and is printed after the subcommand with `--timing`.
+pub fn handle(conf: Config, matches: ArgMatches, mut timings: Timings) {
+    inquire::set_global_render_config(handle_result(conf.theme.render_config()));
+    if matches.get_flag("porcelain") {
+        format::set_porcelain();
+    }
+    if let Some((name, args)) = matches.subcommand() {
+        if cli::build().find_subcommand(name).is_none() {
+            let args = args
+                .get_many::<OsString>("")
+                .map(|a| a.cloned().collect())
+                .unwrap_or_default();
+            exit(handle_result(plugin::run(&conf, name, args)));
+        }
+        // runs on every prompt render so it must not scan the root
+        if name == "prompt-segment" {
+            return prompt_segment(&conf, args);
+        }
+        if name == "touch-if-project" {
+            return touch_if_project(&conf, args);
+        }
+        if name == "shell-init" {
+            return shell_init(args);
+        }
+        if name == "diff-config" {
+            return diff_config(&conf, &matches);
+        }
+        // before the scan, which would already rewrite the index of root
+        if name == "gc" {
+            return gc(&conf);
+        }
+    }
+    let rules = handle_result(conf.tag_rules());
+    let progress = Progress::new(matches.get_flag("quiet"));
+    let mut manager = handle_result(timings.time("scan", || {
+        ProjectManager::load_with_progress(
+            Path::new(&conf.dir).to_owned(),
+            Box::new(Disk),
+            &mut |done, total, dir| {
+                progress.update(done, total, &dir.to_string_lossy());
+            },
+        )
+    }));
+    drop(progress);
+    timings.set_scan(manager.scan_stats());
+    let mut warnings: Vec<String> = manager
+        .load_errors()
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    let paths = manager
+        .iter_projects(SortOrder::Name)
+        .map(|p| {
+            let path = manager.get_path(p.get_name());
+            (path.canonicalize().unwrap_or(path), p.get_name().to_owned())
+        })
+        .collect();
+    if let Err(e) = index::save(Path::new(&conf.dir), paths) {
+        warnings.push(format!("couldn't update project index: {}", e));
+    }
+    let skipped = manager
+        .load_errors()
+        .iter()
+        .filter(|e| e.severity == Severity::Error)
+        .count();
+    if matches.get_flag("strict") && skipped > 0 {
+        handle_result::<(), _>(Err(format!("{} project(s) couldn't be loaded", skipped)));
+    }
+    if let Some(days) = conf.trash_keep_days {
+        if let Err(e) = manager.empty_trash(Some(Duration::days(days as i64))) {
+            warnings.push(format!("couldn't purge trash: {}", e));
+        }
+    }
+    // doctor reports them itself
+    if matches.subcommand_name() != Some("doctor") {
+        for warning in &warnings {
+            error::warn(warning);
+        }
+    }
+    let unnormalized = match conf.normalize_tags {
+        true => manager.normalize_tags(),
+        false => Vec::new(),
+    };
+    let mut hooks = handle_result(Hooks::load(&conf.scripts));
+    hooks.set_projects(
+        &manager
+            .iter_projects(SortOrder::Name)
+            .map(|p| {
+                let path = manager.get_path(p.get_name());
+                (p.clone(), path)
+            })
+            .collect::<Vec<_>>(),
+    );
+    handle_result(manager.retain(|p, path| hooks.keep_project(p, &path)));
+    let style = match format::porcelain() {
+        true => TimeStyle::porcelain(),
+        false => handle_result(TimeStyle::new(
+            conf.relative_time && !matches.get_flag("absolute"),
+            &conf.time_format,
+        )),
+    };
     if let Some((subcommand, args)) = matches.subcommand() {
-        match subcommand {
-            "create" => create(manager, args),
+        timings.time(subcommand, || match subcommand {
+            "create" => create(
+                manager.with_scaffold(handle_result(conf.scaffold_files())),
+                &hooks,
+                &rules,
+                conf.slugify_names,
+                handle_result(conf.name_pattern()).as_ref(),
+                |manager| {
+                    let today = OffsetDateTime::now_utc().to_offset(style.offset()).date();
+                    conf.auto_name(&format::format_date(today), |name| {
+                        manager.get_project(name).is_some() || manager.get_path(name).exists()
+                    })
+                },
+                args,
+            ),
             "rename" => rename(manager, args),
-            "modify" => modify(manager, args),
-            "exec" => exec(manager, conf.exec, args),
-            "find" => search(manager, conf.exec, args),
+            "modify" => modify(manager, &rules, conf.crypt.as_ref(), args),
+            "reveal" => reveal(manager, conf.crypt.as_ref(), args),
+            "exec" => exec(manager, &conf, args),
+            "find" => search(manager, &conf, &style, &hooks, &rules, args),
+            "du" => du(manager, args),
+            "clean" => clean(manager, &conf.cleaners, args),
+            "report" => report(manager, &style, args),
+            "stats" => show_stats(manager, &style, args),
+            "summary" => summary(manager, &style, args),
+            "workspace" => make_workspace(manager, args),
+            "current" => current(manager, args),
+            "init" => init(manager, &hooks, &rules, args),
+            "history" => show_history(&style, args),
+            "check-names" => check_names(manager, &conf, args),
+            "serve" => handle_result(serve::serve_stdio(manager)),
+            "path" => path(manager, args),
+            "show" => show(manager, &style, args),
+            "exists" => exists(manager, args),
+            "archive" => archive(manager, args),
+            "restore" => restore(manager, args),
+            "delete" => delete(manager, &hooks, args),
+            "trash" => trash(manager, &style, args),
+            "backup" => backup(manager, &conf, args),
+            "migrate-root" => migrate_root(manager, &conf, args),
+            "due" => due(manager, &style, args),
+            "list" => list(manager, &warnings, args),
+            "search" => grep(manager, args),
+            "run" => run(manager, &conf, args),
+            "doctor" => doctor(manager.get_root(), &warnings),
+            "random" => random(manager, &conf, &style, args),
+            "touch" => touch(manager, &style, args),
+            "export" => export(manager, args),
+            "worktree" => worktree(manager, &conf, args),
+            "edit" => edit(manager, &conf.editor(), &rules, args),
+            "tag" => match args.subcommand() {
+                Some(("list", args)) => list_tags(manager, args),
+                Some(("cloud", _)) => tag_cloud(manager),
+                Some(("normalize", _)) => normalize_tags(manager, unnormalized),
+                Some(("rename", args)) => rename_tag(manager, &rules, args),
+                Some(("rules", args)) => match args.subcommand() {
+                    Some(("check", args)) => check_tag_rules(manager, &rules, args),
+                    _ => unreachable!("tag rules requires a subcommand"),
+                },
+                _ => unreachable!("tag requires a subcommand"),
+            },
             _ => panic!("such subcommand({}) doesn't exist", subcommand),
-        };
+        });
+    }
+    if matches.get_flag("timing") {
+        eprint!("{}", timings.report());
     }
 }
@@ -0,0 +1,82 @@
+//! Compressed copies of project directories(zstd-compressed tarballs), used
+//! by `archive --compress` through [`crate::storage::Disk`].
+
+use std::{fs::File, path::Path};
+
+/// Directory in root which archived projects are moved into.
+pub const ARCHIVE_DIR: &str = "archive";
+/// Name of the tarball inside the directory of a compressed project.
+pub const ARCHIVE_FILE: &str = "project.tar.zst";
+
+/// Writes everything inside `dir` to the tarball `file`. Symlinks are stored
+/// as links instead of being followed. The tarball is synced to disk and read
+/// back before returning, so `dir` can be removed afterwards.
+pub fn compress(dir: &Path, file: &Path) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("Couldn't compress {:?} into {:?}: {}", dir, file, e);
+    let mut encoder = zstd::Encoder::new(File::create(file).map_err(err)?, 0).map_err(err)?;
+    encoder.include_checksum(true).map_err(err)?;
+    let mut tar = tar::Builder::new(encoder);
+    tar.follow_symlinks(false);
+    tar.append_dir_all(".", dir).map_err(err)?;
+    let file_handle = tar.into_inner().map_err(err)?.finish().map_err(err)?;
+    file_handle.sync_all().map_err(err)?;
+    verify(file)
+}
+
+/// Reads every entry of the tarball `file`, failing if it's truncated or
+/// corrupted.
+pub fn verify(file: &Path) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("Archive {:?} is broken: {}", file, e);
+    let decoder = zstd::Decoder::new(File::open(file).map_err(err)?).map_err(err)?;
+    let mut archive = tar::Archive::new(decoder);
+    for entry in archive.entries().map_err(err)? {
+        std::io::copy(&mut entry.map_err(err)?, &mut std::io::sink()).map_err(err)?;
+    }
+    // the checksum at the end of the frame is only checked once it's read
+    std::io::copy(&mut archive.into_inner(), &mut std::io::sink()).map_err(err)?;
+    Ok(())
+}
+
+/// Extracts the tarball `file` into `dir`, which is created if needed.
+pub fn extract(file: &Path, dir: &Path) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("Couldn't extract {:?} into {:?}: {}", file, dir, e);
+    let decoder = zstd::Decoder::new(File::open(file).map_err(err)?).map_err(err)?;
+    tar::Archive::new(decoder).unpack(dir).map_err(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn compressed_directory_extracts_again() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dir, file, out) = (
+            tmp.path().join("proj"),
+            tmp.path().join(ARCHIVE_FILE),
+            tmp.path().join("out"),
+        );
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        compress(&dir, &file).unwrap();
+        extract(&file, &out).unwrap();
+        assert_eq!(
+            fs::read_to_string(out.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+    }
+
+    #[test]
+    fn truncated_archive_fails_verification() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dir, file) = (tmp.path().join("proj"), tmp.path().join(ARCHIVE_FILE));
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("data"), "x".repeat(100_000)).unwrap();
+        compress(&dir, &file).unwrap();
+        let data = fs::read(&file).unwrap();
+        fs::write(&file, &data[..data.len() - 8]).unwrap();
+        assert!(verify(&file).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Snapshots of the metadata of every project and of the index, taken by
+//! `backup` so bad batch edits can be rolled back with `backup restore`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
+
+use crate::{index, paths, project::PROJECT_FILE};
+
+const BACKUP_DIR: &str = "backups";
+const METADATA_FILE: &str = "metadata.json";
+const INDEX_FILE: &str = "index.json";
+const TIMESTAMP: &[FormatItem] = format_description!("[year][month][day]-[hour][minute][second]");
+
+fn dir() -> Result<PathBuf, String> {
+    paths::state(BACKUP_DIR)
+        .ok_or_else(|| "Couldn't retrieve state location for your system".into())
+}
+
+/// Timestamps of the snapshots, oldest first.
+pub fn list() -> Result<Vec<String>, String> {
+    let dir = dir()?;
+    let mut res: Vec<String> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|e| e.path().join(METADATA_FILE).is_file())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    res.sort();
+    Ok(res)
+}
+
+/// Saves the metadata files of projects(by project directory) and the index
+/// as a new snapshot, then removes all but the last `keep` snapshots.
+/// Returns the timestamp of the snapshot.
+pub fn create(metadata: &BTreeMap<PathBuf, String>, keep: usize) -> Result<String, String> {
+    let err = |path: &Path, e: std::io::Error| format!("Couldn't write {:?}: {}", path, e);
+    let base = OffsetDateTime::now_utc().format(TIMESTAMP).unwrap();
+    let mut timestamp = base.clone();
+    // more than one snapshot in a second
+    for n in 2.. {
+        if !dir()?.join(&timestamp).exists() {
+            break;
+        }
+        timestamp = format!("{}-{}", base, n);
+    }
+    let snapshot = dir()?.join(&timestamp);
+    fs::create_dir_all(&snapshot).map_err(|e| err(&snapshot, e))?;
+    if let Some(index) = index::path().filter(|p| p.is_file()) {
+        fs::copy(&index, snapshot.join(INDEX_FILE)).map_err(|e| err(&snapshot, e))?;
+    }
+    let file = snapshot.join(METADATA_FILE);
+    fs::write(&file, serde_json::to_string_pretty(metadata).unwrap()).map_err(|e| err(&file, e))?;
+    let snapshots = list()?;
+    for old in &snapshots[..snapshots.len().saturating_sub(keep)] {
+        let path = dir()?.join(old);
+        fs::remove_dir_all(&path).map_err(|e| format!("Couldn't remove {:?}: {}", path, e))?;
+    }
+    Ok(timestamp)
+}
+
+/// Writes the metadata files and the index of snapshot `timestamp` back.
+/// Returns the number of restored projects and the directories which don't
+/// exist anymore and were skipped.
+pub fn restore(timestamp: &str) -> Result<(usize, Vec<PathBuf>), String> {
+    if !list()?.iter().any(|t| t == timestamp) {
+        return Err(format!("No snapshot {}, see `backup list`", timestamp));
+    }
+    let snapshot = dir()?.join(timestamp);
+    let data = fs::read_to_string(snapshot.join(METADATA_FILE))
+        .map_err(|e| format!("Couldn't read snapshot {}: {}", timestamp, e))?;
+    let metadata: BTreeMap<PathBuf, String> =
+        serde_json::from_str(&data).map_err(|e| format!("Broken snapshot {}: {}", timestamp, e))?;
+    let (mut restored, mut missing) = (0, Vec::new());
+    for (dir, data) in metadata {
+        if !dir.is_dir() {
+            missing.push(dir);
+            continue;
+        }
+        let file = dir.join(PROJECT_FILE);
+        fs::write(&file, data).map_err(|e| format!("Couldn't write {:?}: {}", file, e))?;
+        restored += 1;
+    }
+    if let Some(index) = index::path().filter(|_| snapshot.join(INDEX_FILE).is_file()) {
+        fs::copy(snapshot.join(INDEX_FILE), &index)
+            .map_err(|e| format!("Couldn't write {:?}: {}", index, e))?;
+    }
+    Ok((restored, missing))
+}
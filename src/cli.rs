@@ -1,12 +1,24 @@
+use std::path::PathBuf;
+
 use clap::{command, Arg, ArgAction, ArgGroup, Command};
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::{
+    format::{self, Column, Field, OutputFormat},
+    project::{SortOrder, Substitution},
+    workspace::VSCODE_PRESET,
+};
 
 // TODO : exec last accessed project when no argument is passed for exec subcommand
+// required unless the project is given with --id, @optional ones never are
 macro_rules! project_arg {
     ($name:tt,$help:tt) => {
+        project_arg!(@optional $name, $help).required_unless_present("id")
+    };
+    (@optional $name:tt,$help:tt) => {
         Arg::new($name)
             .num_args(1)
             .help($help)
-            .required(true)
             .value_parser(|name: &str| -> Result<String, &str> {
                 if name.ends_with("/") {
                     return Ok(name.strip_suffix("/").unwrap().to_owned());
@@ -15,6 +27,53 @@ macro_rules! project_arg {
             })
     };
 }
+macro_rules! tags_arg {
+    ($help:tt) => {
+        Arg::new("tags")
+            .long("tags")
+            .short('t')
+            .help($help)
+            .num_args(1)
+            .value_delimiter(',')
+    };
+}
+macro_rules! min_priority_arg {
+    () => {
+        Arg::new("min-priority")
+            .long("min-priority")
+            .help("only include projects with at least this priority")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u8))
+    };
+}
+macro_rules! all_arg {
+    () => {
+        Arg::new("all")
+            .long("all")
+            .help("include hidden projects")
+            .action(ArgAction::SetTrue)
+    };
+}
+macro_rules! completed_arg {
+    () => {
+        Arg::new("completed")
+            .long("completed")
+            .help("only include completed projects, or with =no only the others")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("yes")
+            .value_parser(["yes", "no"])
+    };
+}
+macro_rules! modified_since_arg {
+    () => {
+        Arg::new("modified-since")
+            .long("modified-since")
+            .help("only include projects whose metadata changed in this long, like 3d, 1w or 1m")
+            .num_args(1)
+            .value_parser(format::parse_duration)
+    };
+}
 macro_rules! find_flag {
     ($name:tt,$help:tt) => {
         Arg::new($name)
@@ -28,51 +87,679 @@ macro_rules! find_flag {
 pub fn build() -> Command {
     command!()
         .arg_required_else_help(true)
+        .allow_external_subcommands(true)
+        .arg(Arg::new("profile")
+            .long("profile")
+            .short('p')
+            .env("PM_PROFILE")
+            .help("use values from this profile of the config file")
+            .num_args(1)
+            .global(true))
+        .arg(Arg::new("id")
+            .long("id")
+            .help("refer to the project by its id(see list --fields id) instead of its name")
+            .num_args(1)
+            .global(true))
+        .arg(Arg::new("absolute")
+            .long("absolute")
+            .help("show full dates instead of relative times like \"3 days ago\"")
+            .action(ArgAction::SetTrue)
+            .global(true))
+        .arg(Arg::new("quiet")
+            .long("quiet")
+            .short('q')
+            .help("don't show progress bars")
+            .action(ArgAction::SetTrue)
+            .global(true))
+        .arg(Arg::new("porcelain")
+            .long("porcelain")
+            .help("output for scripts which stays the same between versions: no colors, progress bars or truncated names and times in UTC like 2025-07-01T12:00:00Z")
+            .action(ArgAction::SetTrue)
+            .global(true))
+        .arg(Arg::new("strict")
+            .long("strict")
+            .help("fail instead of skipping projects with broken metadata (errors only, warnings like colliding names don't fail)")
+            .action(ArgAction::SetTrue)
+            .global(true))
+        .arg(Arg::new("timing")
+            .long("timing")
+            .help("print how long loading the config, scanning the root and the subcommand took")
+            .action(ArgAction::SetTrue)
+            .global(true))
         .subcommand(
             Command::new("create")
                 .short_flag('C')
                 .about("Create a new project")
-                .arg(project_arg!("project-name", "name of the project and its directory. you can also initiate a project using this command")),
+                .arg(project_arg!(@optional "project-name", "name of the project and its directory. you can also initiate a project using this command").required_unless_present_any(["id", "auto"]))
+                .arg(Arg::new("auto")
+                    .long("auto")
+                    .help("name the project after name_template from config, like scratch-2025-07-01-3")
+                    .conflicts_with("project-name")
+                    .action(ArgAction::SetTrue))
+                .arg(tags_arg!("comma separated tags of the project, instead of choosing them interactively"))
+                .arg(Arg::new("from")
+                    .long("from")
+                    .help("move this existing directory into root as the project")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(PathBuf)))
+                .arg(Arg::new("copy")
+                    .long("copy")
+                    .help("copy the --from directory instead of moving it")
+                    .requires("from")
+                    .action(ArgAction::SetTrue)),
         ).subcommand(
         Command::new("rename")
             .about("Rename an existing project(will change project directory)")
             .short_flag('R')
-            .arg(project_arg!("project-name", "name of the existing project"))
-            .arg(project_arg!("new-name", "new name of the project")),
+            .arg(project_arg!(@optional "project-name", "name of the existing project").required_unless_present_any(["pattern", "id"]))
+            .arg(project_arg!(@optional "new-name", "new name of the project").required_unless_present_any(["pattern", "id"]))
+            .arg(Arg::new("pattern")
+                .long("pattern")
+                .help("rename every project matching a sed-like substitution like 's/^old-/new-/', after showing the new names")
+                .num_args(1)
+                .conflicts_with_all(["project-name", "new-name"])
+                .value_parser(|s: &str| s.parse::<Substitution>()))
+            .arg(Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .help("with --pattern, only rename projects with this tag(can be repeated)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .requires("pattern"))
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .help("with --pattern, only show the new names")
+                .action(ArgAction::SetTrue)
+                .requires("pattern"))
+            .arg(Arg::new("suffix")
+                .long("suffix")
+                .help("if the new name is taken, append -1, -2… until it isn't instead of asking what to do")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("pattern"))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("rename even if the project is locked")
+                .action(ArgAction::SetTrue)),
     ).subcommand(
         Command::new("modify")
             .about("Modify tags of existing projects")
             .short_flag('M')
             .arg(project_arg!("project-name", "name of the project to modify"))
+            .arg(tags_arg!("comma separated tags replacing the current ones, instead of choosing them interactively"))
+            .arg(Arg::new("priority")
+                .long("priority")
+                .help("set priority of the project(higher is more important), asks for it if no value is given")
+                .num_args(0..=1)
+                .value_parser(clap::value_parser!(u8)))
+            .arg(Arg::new("due")
+                .long("due")
+                .help("set due date of the project like 2025-07-01, or none to remove it")
+                .num_args(1)
+                .value_parser(|s: &str| match s {
+                    "none" => Ok(None),
+                    _ => format::parse_date(s).map(Some),
+                }))
+            .arg(Arg::new("completed")
+                .long("completed")
+                .help("mark the project as completed today, on a date like 2025-07-01, or with none as not completed")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("today")
+                .value_parser(|s: &str| match s {
+                    "none" => Ok(None),
+                    "today" => Ok(Some(OffsetDateTime::now_utc()
+                        .to_offset(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC))
+                        .date())),
+                    _ => format::parse_date(s).map(Some),
+                }))
+            .arg(Arg::new("owner")
+                .long("owner")
+                .help("set the user responsible for the project, or none to remove it")
+                .num_args(1)
+                .value_parser(|s: &str| match s {
+                    "none" => Ok::<_, String>(None),
+                    _ => Ok(Some(s.to_owned())),
+                }))
+            .arg(Arg::new("locked")
+                .long("locked")
+                .help("with true, lock the project so it can't be renamed, modified or deleted without --unlock, with false remove the lock")
+                .num_args(1)
+                .value_parser(clap::value_parser!(bool)))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("modify even if the project is locked, keeping the lock")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("hide")
+                .long("hide")
+                .help("hide the project from find and list unless --all is given")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("unhide"))
+            .arg(Arg::new("unhide")
+                .long("unhide")
+                .help("show the project in find and list again")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("encrypt")
+                .long("encrypt")
+                .help("encrypt the title and custom values of the project with crypt from config")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("decrypt"))
+            .arg(Arg::new("decrypt")
+                .long("decrypt")
+                .help("store the encrypted title and custom values of the project in plain text again")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("reveal")
+            .about("Print the encrypted title and custom values of a project")
+            .arg(project_arg!("project-name", "name of the project"))
     ).subcommand(
         Command::new("exec")
             .about("Execute in a project")
             .short_flag('E')
             .arg(Arg::new("command")
-                .short('c').help("command to execute in project directory. runs program specified in config(exec value)")
+                .short('c').help("command to execute in project directory. runs tag_exec of its tags or exec from config by default, or your shell($SHELL) if neither is set")
                 .required(false)
                 .num_args(1)
                 .default_value(""))
+            .arg(Arg::new("preset")
+                .long("preset")
+                .help("open project in a predefined way instead of running a command")
+                .num_args(1)
+                .value_parser([VSCODE_PRESET])
+                .conflicts_with("command"))
+            .arg(Arg::new("again")
+                .long("again")
+                .help("run the command last run in the project with -c again")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["command", "preset"]))
+            .arg(Arg::new("cd")
+                .long("cd")
+                .help("run in this directory of the project instead of its root, like backend/")
+                .num_args(1)
+                .value_name("dir")
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("explain")
+                .long("explain")
+                .help("print the executable, arguments, directory and environment the command would run with instead of running it")
+                .action(ArgAction::SetTrue))
             .arg(project_arg!("project-name", "name of the project"))
     ).subcommand(
         Command::new("find")
             .short_flag('F')
             .about("interactive prompt to look for a project based on name and tags and then do something with it")
+            .arg(Arg::new("query")
+                .help("start with only the projects matching this, as if it was typed into the prompt"))
             .arg(find_flag!("invert", "reverse order of projects"))
             .arg(find_flag!("created", "sort projects by time created"))
             .arg(find_flag!("accessed", "sort projects by last time accessed using this program(default option)"))
             .arg(find_flag!("name","sort projects by name"))
+            .arg(Arg::new("sort")
+                .long("sort")
+                .help("comma separated keys to sort by, later ones break ties: created, accessed, modified, completed, name, priority")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(|s: &str| s.parse::<SortOrder>()))
             .group(
-                ArgGroup::new("order").args(["created", "accessed", "name"]).required(false).multiple(false)
+                ArgGroup::new("order").args(["created", "accessed", "name", "sort"]).required(false).multiple(false)
             )
+            .arg(min_priority_arg!())
+            .arg(completed_arg!())
+            .arg(modified_since_arg!())
+            .arg(all_arg!())
+            .arg(Arg::new("group-by")
+                .long("group-by")
+                .help("show projects in sections, under their first tag")
+                .num_args(1)
+                .value_parser(["tag"]))
+            .arg(Arg::new("by-tag")
+                .long("by-tag")
+                .help("choose a tag first, then one of the projects having it")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("group-by"))
             .arg(find_flag!("rename", "rename selected project"))
             .arg(find_flag!("modify", "modify tags of selected project"))
+            .arg(find_flag!("delete", "move selected project to the trash after confirming").long("delete"))
+            .arg(Arg::new("copy")
+                .short('y')
+                .long("copy")
+                .help("copy path of selected project to the clipboard")
+                .action(ArgAction::SetTrue))
             .arg(Arg::new("execute")
                 .short('e')
                 .help("execute command in selected project directory(runs program specified in config if not specified. is default action)")
                 .num_args(1)
                 .required(false).default_value(""))
+            .arg(Arg::new("many")
+                .long("many")
+                .help("choose several projects and run the command of -e in all of them at once, or a single time with their paths in place of a {paths} argument")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["rename", "modify", "delete", "copy"]))
+            .group(
+                ArgGroup::new("action").args(["rename", "modify", "delete", "copy", "execute"]).required(false).multiple(false))
+    ).subcommand(
+        Command::new("du")
+            .about("Show disk usage of projects(sizes are cached between runs)")
+            .arg(find_flag!("name", "sort projects by name instead of size"))
+            .arg(find_flag!("invert", "reverse order of projects"))
+            .arg(find_flag!("refresh", "measure sizes again instead of using cached values"))
+    ).subcommand(
+        Command::new("clean")
+            .about("Run cleanup rules from config(cargo clean, removing node_modules, ...) to reclaim space")
+            .arg(project_arg!(@optional "project-name", "name of the project to clean"))
+            .arg(Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .help("clean every project with this tag")
+                .num_args(1))
+            .group(
+                ArgGroup::new("target").args(["project-name", "tag", "id"]).required(true).multiple(false))
+    ).subcommand(
+        Command::new("report")
+            .about("Print a table of projects as csv or markdown")
+            .arg(Arg::new("format")
+                .long("format")
+                .short('f')
+                .help("output format: csv or md")
+                .num_args(1)
+                .default_value("md")
+                .value_parser(|s: &str| s.parse::<OutputFormat>()))
+            .arg(Arg::new("columns")
+                .long("columns")
+                .short('c')
+                .help("comma separated columns to include: name, tags, created, accessed, size, status, due, owner")
+                .num_args(1)
+                .value_delimiter(',')
+                .default_value("name,tags,created,accessed")
+                .value_parser(|s: &str| s.parse::<Column>()))
+            .arg(find_flag!("invert", "reverse order of projects"))
+            .arg(find_flag!("accessed", "sort projects by last time accessed using this program"))
+            .arg(find_flag!("name","sort projects by name(default option)"))
+            .arg(Arg::new("sort")
+                .long("sort")
+                .help("comma separated keys to sort by, later ones break ties: created, accessed, modified, completed, name, priority")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(|s: &str| s.parse::<SortOrder>()))
+            .arg(Arg::new("html")
+                .long("html")
+                .help("write a self-contained HTML dashboard(sortable table, tag cloud and activity) to this file instead")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .group(
+                ArgGroup::new("order").args(["accessed", "name", "sort"]).required(false).multiple(false))
+    ).subcommand(
+        Command::new("stats")
+            .about("Show statistics about projects")
+            .arg(Arg::new("heatmap")
+                .long("heatmap")
+                .help("show a calendar heatmap of project activity")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("weeks")
+                .long("weeks")
+                .short('w')
+                .help("number of weeks shown in heatmap")
+                .num_args(1)
+                .default_value("26")
+                .value_parser(clap::value_parser!(u16).range(1..)))
+    ).subcommand(
+        Command::new("summary")
+            .about("Summarize which projects were created, renamed, modified or touched recently, for status updates")
+            .arg(Arg::new("since")
+                .long("since")
+                .short('s')
+                .help("length of the period ending now, like 3d, 1w or 1m")
+                .num_args(1)
+                .default_value("1w")
+                .value_parser(format::parse_duration))
+    ).subcommand(
+        Command::new("serve")
+            .about("Serve list/search/open/modify over JSON-RPC for editor integrations")
+            .arg(Arg::new("stdio")
+                .long("stdio")
+                .help("communicate over stdin and stdout(one request per line)")
+                .action(ArgAction::SetTrue)
+                .required(true))
+    ).subcommand(
+        Command::new("workspace")
+            .about("Generate a VS Code workspace containing several projects")
+            .arg(project_arg!("project-name", "names of projects to include").num_args(1..))
+            .arg(Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("where to write the workspace file(defaults to a file in cache directory)")
+                .num_args(1))
+            .arg(Arg::new("open")
+                .long("open")
+                .help("open the workspace with `code` after generating it")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("prompt-segment")
+            .about("Print name and primary tag of the project containing current directory, for shell prompts. prints nothing outside projects")
+            .arg(Arg::new("format")
+                .long("format")
+                .short('f')
+                .help("format of the segment. {name} and {tag} are replaced")
+                .num_args(1)
+                .default_value("{name} ({tag})"))
+    ).subcommand(
+        Command::new("shell-init")
+            .about("Print shell code defining `pjcd <project>`, add it to your shell's rc file like: eval \"$(pj shell-init bash --hook)\"")
+            .arg(Arg::new("shell")
+                .help("shell to print code for")
+                .num_args(1)
+                .required(true)
+                .value_parser(["bash", "zsh", "fish"]))
+            .arg(Arg::new("hook")
+                .long("hook")
+                .help("also mark projects as accessed whenever you cd into them")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("touch-if-project")
+            .about("Mark the project containing a directory as accessed, silently doing nothing outside projects. used by shell-init --hook")
+            .hide(true)
+            .arg(Arg::new("dir")
+                .help("directory inside the project(defaults to current directory)")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf)))
+    ).subcommand(
+        Command::new("config")
+            .about("Work with the config file")
+            .subcommand_required(true)
+            .subcommand(Command::new("schema").about("Print JSON Schema of the config file"))
+    ).subcommand(
+        Command::new("diff-config")
+            .about("Print the resolved config, with whether each value comes from the config file, a profile, a flag or the defaults")
+    ).subcommand(
+        Command::new("gc")
+            .about("Remove entries of directories deleted by hand from the project index(with its cached sizes), trash journal and external projects")
+    ).subcommand(
+        Command::new("migrate-root")
+            .about("Move every project in root into another directory and make it the root, or only point the config at it if they were moved by hand")
+            .arg(Arg::new("new-dir")
+                .required(true)
+                .help("new root directory, created if needed"))
+            .arg(Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .help("don't ask for confirmation")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("current")
+            .about("Print name of the project containing current directory")
+            .arg(Arg::new("path")
+                .long("path")
+                .help("print path of the project instead of its name")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("init")
+            .about("Turn current directory into a project, even if it's outside the root directory")
+            .arg(Arg::new("name")
+                .long("name")
+                .short('n')
+                .help("name of the project(defaults to name of the directory)")
+                .num_args(1))
+            .arg(tags_arg!("comma separated tags of the project, instead of choosing them interactively"))
+    ).subcommand(
+        Command::new("check-names")
+            .about("List projects whose names don't follow name_pattern or slugify_names of the config, asking for new names when run in a terminal")
+            .arg(Arg::new("fix")
+                .long("fix")
+                .help("rename them to their slug instead of asking")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("history")
+            .about("Show changes made to projects by this program")
+            .arg(project_arg!(@optional "project-name", "only show changes of this project"))
+    ).subcommand(
+        Command::new("tag")
+            .about("Work with tags")
+            .subcommand_required(true)
+            .subcommand(Command::new("list")
+                .about("List tags of all projects")
+                .arg(Arg::new("counts")
+                    .long("counts")
+                    .help("show how many projects use each tag, most used first")
+                    .action(ArgAction::SetTrue)))
+            .subcommand(Command::new("cloud")
+                .about("Show tags of all projects as a cloud, more used tags are bolder"))
+            .subcommand(Command::new("normalize")
+                .about("Lowercase tags of all projects, merging tags which only differ in case"))
+            .subcommand(Command::new("rename")
+                .about("Rename a tag in all projects and in tag_exec and tag_implications of the config")
+                .arg(Arg::new("old")
+                    .help("tag to rename")
+                    .required(true))
+                .arg(Arg::new("new")
+                    .help("new name of the tag, merged with it if it's used already")
+                    .required(true)))
+            .subcommand(Command::new("rules")
+                .about("Work with tag_implications of the config")
+                .subcommand_required(true)
+                .subcommand(Command::new("check")
+                    .about("List projects missing tags implied by their other tags")
+                    .arg(Arg::new("fix")
+                        .long("fix")
+                        .help("add the missing tags")
+                        .action(ArgAction::SetTrue))))
+    ).subcommand(
+        Command::new("path")
+            .about("Print path of a project")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("copy")
+                .long("copy")
+                .short('y')
+                .help("copy the path to the clipboard instead")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("show")
+            .about("Show metadata of a project")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("print the metadata, id and path as a JSON object")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("exists")
+            .about("Exit with 0 if a project with exactly this name exists and 1 otherwise, for scripts")
+            .arg(Arg::new("project-name")
+                .help("name of the project")
+                .required(true)
+                .num_args(1))
+            .arg(Arg::new("path")
+                .long("path")
+                .help("also require the project directory to exist")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("edit")
+            .about("Edit metadata of a project in your editor")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("edit even if the project is locked")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("list")
+            .about("Print projects one per line, for scripts")
+            .arg(Arg::new("fields")
+                .long("fields")
+                .short('f')
+                .help("comma separated fields to print, separated by tabs: id, name, title, path, tags, created, accessed, modified, completed, due, priority, owner, custom.<key>")
+                .num_args(1)
+                .value_delimiter(',')
+                .default_value("name")
+                .value_parser(|s: &str| s.parse::<Field>()))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("print a JSON object with the projects and the warnings found while loading them")
+                .action(ArgAction::SetTrue))
+            .arg(min_priority_arg!())
+            .arg(completed_arg!())
+            .arg(modified_since_arg!())
+            .arg(all_arg!())
+            .arg(Arg::new("owner")
+                .long("owner")
+                .help("only list projects owned by this user")
+                .num_args(1))
+            .arg(Arg::new("sort")
+                .long("sort")
+                .help("comma separated keys to sort by, later ones break ties: created, accessed, modified, completed, name, priority")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(|s: &str| s.parse::<SortOrder>()))
+    ).subcommand(
+        Command::new("due")
+            .about("Show overdue projects and the ones due soon, closest deadline first")
+            .arg(Arg::new("days")
+                .long("days")
+                .short('d')
+                .help("include projects due in this many days")
+                .num_args(1)
+                .default_value("30")
+                .value_parser(clap::value_parser!(i64)))
+    ).subcommand(
+        Command::new("archive")
+            .about("Move a project into the archive directory of root, or list archived projects")
+            .arg(project_arg!(@optional "project-name", "name of the project(lists archived projects if missing)"))
+            .arg(Arg::new("compress")
+                .long("compress")
+                .help("only keep a zstd-compressed tarball of the project")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("archive even if the project has uncommitted or unpushed git changes")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("archive even if the project is locked")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("restore")
+            .about("Move an archived project back into root")
+            .arg(project_arg!("project-name", "name of the archived project"))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("restore even if the project is locked")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("search")
+            .about("Print names of projects whose name, title or tags match a pattern, without prompting")
+            .arg(Arg::new("pattern")
+                .help("text to look for(case-insensitive), or a regex with --regex")
+                .num_args(1)
+                .required(true))
+            .arg(Arg::new("regex")
+                .long("regex")
+                .short('r')
+                .help("treat pattern as a regular expression")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .help("only include projects with this tag(can be repeated)")
+                .num_args(1)
+                .action(ArgAction::Append))
+            .arg(Arg::new("sort")
+                .long("sort")
+                .help("comma separated keys to sort by, later ones break ties: created, accessed, modified, completed, name, priority")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(|s: &str| s.parse::<SortOrder>()))
+    ).subcommand(
+        Command::new("random")
+            .about("Open a random project, picking ones not accessed for a long time more often")
+            .arg(Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .help("only pick projects with this tag(can be repeated)")
+                .num_args(1)
+                .action(ArgAction::Append))
+            .arg(Arg::new("print")
+                .long("print")
+                .help("only print the name of the picked project")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("doctor")
+            .about("Check projects for problems like broken metadata, names colliding on case-insensitive filesystems or a root inside a project or git repository")
+    ).subcommand(
+        Command::new("run")
+            .about("Run a task from the justfile, Makefile or package.json scripts of a project")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("task")
+                .help("task to run(choose from the discovered ones if missing)")
+                .num_args(1))
+    ).subcommand(
+        Command::new("worktree")
+            .about("Work with git worktrees of projects(find asks which one to open)")
+            .subcommand_required(true)
+            .subcommand(Command::new("add")
+                .about("Check out a branch into a new worktree at worktree_dir from config")
+                .arg(project_arg!("project-name", "name of the project"))
+                .arg(Arg::new("branch")
+                    .help("branch to check out, created from HEAD if it doesn't exist")
+                    .num_args(1)
+                    .required_unless_present("id")))
+            .subcommand(Command::new("list")
+                .about("List worktrees of a project")
+                .arg(project_arg!("project-name", "name of the project")))
+    ).subcommand(
+        Command::new("export")
+            .about("Write a markdown note per project and an index into a directory, e.g. an Obsidian vault")
+            .arg(Arg::new("obsidian")
+                .long("obsidian")
+                .help("directory to write notes into, linked with [[wiki links]]")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf)))
+            .arg(Arg::new("markdown")
+                .long("markdown")
+                .help("directory to write notes into, linked with plain markdown links")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf)))
             .group(
-                ArgGroup::new("action").args(["rename", "modify", "execute"]).required(false).multiple(false)))
-        .after_help("Note: to delete a project, just delete the directory containing it")
+                ArgGroup::new("target").args(["obsidian", "markdown"]).required(true).multiple(false))
+    ).subcommand(
+        Command::new("touch")
+            .about("Mark a project as accessed now, or at a given time")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("at")
+                .long("at")
+                .help("time to set instead of now: YYYY-MM-DD, \"YYYY-MM-DD HH:MM\"(local time) or RFC 3339")
+                .num_args(1))
+    ).subcommand(
+        Command::new("delete")
+            .about("Move a project into the trash")
+            .arg(project_arg!("project-name", "name of the project"))
+            .arg(Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("delete even if the project has uncommitted or unpushed git changes")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("unlock")
+                .long("unlock")
+                .help("delete even if the project is locked")
+                .action(ArgAction::SetTrue))
+    ).subcommand(
+        Command::new("trash")
+            .about("Work with deleted projects(purged after trash_keep_days from config)")
+            .subcommand_required(true)
+            .subcommand(Command::new("list")
+                .about("List deleted projects, oldest first"))
+            .subcommand(Command::new("restore")
+                .about("Move a deleted project back to where it was")
+                .arg(project_arg!("project-name", "name of the deleted project")))
+            .subcommand(Command::new("empty")
+                .about("Permanently remove every deleted project"))
+    ).subcommand(
+        Command::new("backup")
+            .about("Save the metadata of every project as a snapshot(keeping the last backup_keep from config)")
+            .subcommand(Command::new("list")
+                .about("List snapshots by timestamp, oldest first"))
+            .subcommand(Command::new("restore")
+                .about("Write the metadata of a snapshot back, saving the current one first")
+                .arg(Arg::new("timestamp")
+                    .help("timestamp of the snapshot, see backup list")
+                    .required(true)))
+    )
+        .after_help("Note: deleted projects are kept in the trash until it's emptied\n\
+            Use `.` as project name to refer to the project containing current directory, or a path like ./name or ~/projects/name\n\
+            Unknown subcommands run `pj-<subcommand>` from PATH if it exists")
 }
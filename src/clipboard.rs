@@ -0,0 +1,15 @@
+//! Copying to the system clipboard, only available when built with the
+//! `clipboard` feature.
+
+/// Puts `text` on the system clipboard.
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| format!("Couldn't copy to clipboard: {}", e))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_: &str) -> Result<(), String> {
+    Err("Copying needs the clipboard feature, which this build doesn't have".into())
+}
@@ -1,19 +1,723 @@
-use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Component, PathBuf},
+};
 
-#[derive(Deserialize, Debug)]
+use inquire::ui::{Color, RenderConfig, StyleSheet, Styled};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{format::TimeStyle, paths};
+
+/// Settings read from the config file. Outside this crate it's made with
+/// [`Config::new`] or [`Config::builder`], so fields can be added to it
+/// without breaking anyone.
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[non_exhaustive]
 pub struct Config {
-    pub dir: String, // root directory
-    pub exec: String, // default program to execute/open projects with
+    /// Root directory containing projects
+    pub dir: String,
+    /// Default program to execute/open projects with, the shell if unset
+    #[serde(default)]
+    pub exec: Option<String>,
+    /// Programs projects with a tag are opened with instead of `exec`, like
+    /// `{"rust": "code {path}"}`. The first tag alphabetically wins
+    #[serde(default)]
+    pub tag_exec: BTreeMap<String, String>,
+    /// Shell used when `exec` and $SHELL aren't set(defaults to /bin/sh, or
+    /// %ComSpec% on Windows)
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Program used by `edit`(defaults to $VISUAL, $EDITOR or vi)
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Per-ecosystem cleanup rules used by `clean`
+    #[serde(default = "default_cleaners")]
+    pub cleaners: Vec<Cleaner>,
+    /// Files written into every new project besides `.gitignore`, like
+    /// `.editorconfig` or `Justfile`
+    #[serde(default)]
+    pub scaffold: Vec<Scaffold>,
+    /// Show "3 days ago" instead of full dates
+    #[serde(default = "default_true")]
+    pub relative_time: bool,
+    /// Format description used to show full dates, like "[year]-[month]-[day]"
+    #[serde(default = "default_time_format")]
+    pub time_format: String,
+    /// Lowercase tags when creating, modifying and loading projects so "Rust"
+    /// and "rust" are the same tag. `tag normalize` applies it to the files
+    #[serde(default = "default_true")]
+    pub normalize_tags: bool,
+    /// Create projects named like "My Cool App" in a directory named
+    /// "my-cool-app", keeping the original as their title
+    #[serde(default)]
+    pub slugify_names: bool,
+    /// Regex names of new projects have to match, `check-names` lists the
+    /// existing ones which don't
+    #[serde(default)]
+    pub name_pattern: Option<String>,
+    /// Name of projects made by `create --auto`, `{date}` is replaced with
+    /// today's date and `{counter}` with the lowest number(from 1) giving a
+    /// name which isn't taken
+    #[serde(default = "default_name_template")]
+    pub name_template: String,
+    /// Regex every tag has to match
+    #[serde(default = "default_tag_pattern")]
+    pub tag_pattern: String,
+    /// Maximum number of characters in a tag
+    #[serde(default)]
+    pub max_tag_length: Option<usize>,
+    /// Tags added along with others, like `{"actix": ["rust"]}`. Implied tags
+    /// are removed again once nothing implies them
+    #[serde(default)]
+    pub tag_implications: BTreeMap<String, Vec<String>>,
+    /// Days deleted projects are kept in the trash before they're purged,
+    /// null keeps them until `trash empty`
+    #[serde(default = "default_trash_keep_days")]
+    pub trash_keep_days: Option<u64>,
+    /// Snapshots kept by `backup`, older ones are removed
+    #[serde(default = "default_backup_keep")]
+    pub backup_keep: usize,
+    /// Where `worktree add` puts worktrees, `{root}`, `{project}` and
+    /// `{branch}` are replaced with the root directory, project and branch
+    #[serde(default = "default_worktree_dir")]
+    pub worktree_dir: String,
+    /// Commands encrypting the title and custom values of projects marked
+    /// with `modify --encrypt`
+    #[serde(default)]
+    pub crypt: Option<Crypt>,
+    /// Look of interactive prompts
+    #[serde(default)]
+    pub theme: Theme,
+    /// Paths of rhai scripts run on events(needs the scripting feature)
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Named sets of values which override the ones above when selected with
+    /// --profile or PM_PROFILE, e.g. a different `dir` for work projects
+    #[serde(default)]
+    pub profiles: HashMap<String, Map<String, Value>>,
+    #[serde(skip)]
+    pub profile: Option<String>, // name of the selected profile
+}
+
+/// A cleanup rule which applies to projects containing `marker` in their root.
+/// `command` is run inside the project directory and `remove` lists
+/// directories(relative to the project) to delete.
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Cleaner {
+    pub marker: String,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// A file created in new projects at `path`(relative to the project) with
+/// either `content` or the contents of the `template` file. `{name}` in them
+/// is replaced with the name of the project. Existing files are kept.
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Scaffold {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+}
+
+/// Commands reading text on stdin and printing it encrypted or decrypted,
+/// like "age -a -r age1…" and "age -d -i /home/me/key.txt", or
+/// "gpg -a -e -r me@example.com" and "gpg -d -q".
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Crypt {
+    pub encrypt: String,
+    pub decrypt: String,
+}
+
+/// Overrides of the default look of prompts. Colors are names like "cyan",
+/// "light_blue" or "dark_grey", or "#rrggbb".
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct Theme {
+    /// Shown before each prompt instead of "?"
+    #[serde(default)]
+    pub prompt_prefix: Option<String>,
+    /// Color of the prompt prefix
+    #[serde(default)]
+    pub prefix_color: Option<String>,
+    /// Color of the highlighted option, selected checkboxes and answers
+    #[serde(default)]
+    pub highlight_color: Option<String>,
+    /// Color of help messages shown under prompts
+    #[serde(default)]
+    pub help_color: Option<String>,
+}
+
+fn parse_color(name: &str) -> Result<Color, String> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+            _ => Err(format!("Invalid color '{}', expected #rrggbb", name)),
+        };
+    }
+    Ok(match name.to_lowercase().replace('-', "_").as_str() {
+        "black" => Color::Black,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "red" | "light_red" => Color::LightRed,
+        "dark_red" => Color::DarkRed,
+        "green" | "light_green" => Color::LightGreen,
+        "dark_green" => Color::DarkGreen,
+        "yellow" | "light_yellow" => Color::LightYellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" | "light_blue" => Color::LightBlue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" | "light_magenta" => Color::LightMagenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" | "light_cyan" => Color::LightCyan,
+        "dark_cyan" => Color::DarkCyan,
+        _ => return Err(format!("Unknown color '{}' in theme", name)),
+    })
+}
+
+impl Theme {
+    /// inquire's colored defaults with the values of the theme applied.
+    pub fn render_config(&self) -> Result<RenderConfig, String> {
+        let mut conf = RenderConfig::default_colored();
+        let color = |c: &Option<String>| c.as_deref().map(parse_color).transpose();
+        if let Some(prefix) = &self.prompt_prefix {
+            // prompts only take static strings, the config lives until exit anyway
+            conf.prompt_prefix.content = Box::leak(prefix.clone().into_boxed_str());
+            conf.answered_prompt_prefix.content = conf.prompt_prefix.content;
+        }
+        if let Some(c) = color(&self.prefix_color)? {
+            conf.prompt_prefix = conf.prompt_prefix.with_fg(c);
+            conf.answered_prompt_prefix = conf.answered_prompt_prefix.with_fg(c);
+        }
+        if let Some(c) = color(&self.highlight_color)? {
+            conf.highlighted_option_prefix = Styled::new(">").with_fg(c);
+            conf.selected_option = Some(StyleSheet::new().with_fg(c));
+            conf.selected_checkbox = conf.selected_checkbox.with_fg(c);
+            conf.answer = StyleSheet::new().with_fg(c);
+        }
+        if let Some(c) = color(&self.help_color)? {
+            conf.help_message = StyleSheet::new().with_fg(c);
+        }
+        Ok(conf)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_time_format() -> String {
+    "[year]-[month]-[day] [hour]:[minute]".into()
+}
+
+fn default_trash_keep_days() -> Option<u64> {
+    Some(30)
+}
+
+fn default_backup_keep() -> usize {
+    10
+}
+
+fn default_worktree_dir() -> String {
+    "{root}/.worktrees/{project}/{branch}".into()
+}
+
+fn default_name_template() -> String {
+    "scratch-{date}-{counter}".into()
+}
+
+fn default_tag_pattern() -> String {
+    r"^\S+$".into()
+}
+
+fn default_cleaners() -> Vec<Cleaner> {
+    vec![
+        Cleaner {
+            marker: "Cargo.toml".into(),
+            command: Some("cargo clean".into()),
+            remove: vec![],
+        },
+        Cleaner {
+            marker: "package.json".into(),
+            command: None,
+            remove: vec!["node_modules".into()],
+        },
+    ]
+}
+
+/// Checks tags against `tag_pattern` and `max_tag_length` of the config and
+/// applies `tag_implications`.
+#[derive(Clone)]
+pub struct TagRules {
+    pattern: Regex,
+    max_length: Option<usize>,
+    implications: BTreeMap<String, Vec<String>>,
+}
+
+impl TagRules {
+    pub fn check(&self, tag: &str) -> Result<(), String> {
+        if let Some(max) = self.max_length.filter(|max| tag.chars().count() > *max) {
+            return Err(format!("Tag '{}' is longer than {} characters", tag, max));
+        }
+        if !self.pattern.is_match(tag) {
+            return Err(format!(
+                "Tag '{}' doesn't match tag_pattern({})",
+                tag, self.pattern
+            ));
+        }
+        Ok(())
+    }
+    /// Tags implied by `tags`, following chains like `actix => rust => code`.
+    pub fn implied(&self, tags: &HashSet<String>) -> HashSet<String> {
+        let mut res = HashSet::new();
+        let mut pending: Vec<&String> = tags.iter().collect();
+        while let Some(tag) = pending.pop() {
+            for implied in self.implications.get(tag).into_iter().flatten() {
+                if res.insert(implied.to_owned()) {
+                    pending.push(implied);
+                }
+            }
+        }
+        res
+    }
+    /// `tags` of a project which had `old` tags, with implied tags added.
+    /// Tags which were only there because `old` implied them are dropped
+    /// unless the new tags still imply them.
+    pub fn imply(&self, old: &HashSet<String>, mut tags: HashSet<String>) -> HashSet<String> {
+        let before = self.implied(old);
+        // repeated so whole chains are dropped, while tags of a cycle keep
+        // implying each other
+        loop {
+            let implied = self.implied(&tags);
+            let count = tags.len();
+            tags.retain(|t| !before.contains(t) || !old.contains(t) || implied.contains(t));
+            if tags.len() == count {
+                tags.extend(implied);
+                return tags;
+            }
+        }
+    }
+}
+
+/// Where a value of the resolved config came from.
+pub enum Source {
+    Default,
+    File,
+    Profile(String),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File => write!(f, "config file"),
+            Source::Profile(name) => write!(f, "profile '{}'", name),
+        }
+    }
+}
+
+/// JSON Schema of the config file, generated from the same serde attributes
+/// used to parse it.
+pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config)).unwrap()
 }
 
 impl Config {
-    pub fn new() -> Config {
-        let path = dirs::config_dir()
-            .expect("Couldn't retrieve config location for your system")
-            .join("cli-project-manager.json");
+    /// Builds a config in code instead of reading the config file.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+    pub fn path() -> PathBuf {
+        paths::config_file()
+    }
+    pub fn editor(&self) -> String {
+        self.editor
+            .clone()
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .unwrap_or_else(|| "vi".into())
+    }
+    pub fn worktree_path(&self, project: &str, branch: &str) -> PathBuf {
+        PathBuf::from(
+            self.worktree_dir
+                .replace("{root}", &self.dir)
+                .replace("{project}", project)
+                .replace("{branch}", branch),
+        )
+    }
+    /// Name from `name_template` for a project made on `date`, skipping
+    /// counters for which `taken` is true.
+    pub fn auto_name(&self, date: &str, taken: impl Fn(&str) -> bool) -> String {
+        let name = self.name_template.replace("{date}", date);
+        if !name.contains("{counter}") {
+            return name;
+        }
+        (1..)
+            .map(|n: u32| name.replace("{counter}", &n.to_string()))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+    /// Paths and contents of the `scaffold` files, reading their templates.
+    pub fn scaffold_files(&self) -> Result<Vec<(PathBuf, String)>, String> {
+        self.scaffold
+            .iter()
+            .map(|file| {
+                if file.path.is_absolute()
+                    || file.path.components().any(|c| c == Component::ParentDir)
+                {
+                    return Err(format!(
+                        "Scaffold file {:?} must be inside the project",
+                        file.path
+                    ));
+                }
+                let content = match (&file.content, &file.template) {
+                    (Some(content), None) => content.clone(),
+                    (None, Some(template)) => std::fs::read_to_string(template).map_err(|e| {
+                        format!("Couldn't read scaffold template {:?}: {}", template, e)
+                    })?,
+                    _ => {
+                        return Err(format!(
+                            "Scaffold file {:?} needs either content or template",
+                            file.path
+                        ))
+                    }
+                };
+                Ok((file.path.clone(), content))
+            })
+            .collect()
+    }
+    pub fn name_pattern(&self) -> Result<Option<Regex>, String> {
+        self.name_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("Invalid name_pattern: {}", e))
+    }
+    pub fn tag_rules(&self) -> Result<TagRules, String> {
+        Ok(TagRules {
+            pattern: Regex::new(&self.tag_pattern)
+                .map_err(|e| format!("Invalid tag_pattern: {}", e))?,
+            max_length: self.max_tag_length,
+            implications: self.tag_implications.clone(),
+        })
+        .and_then(|rules| {
+            for tag in self.tag_implications.values().flatten() {
+                rules
+                    .check(tag)
+                    .map_err(|e| format!("Invalid tag_implications: {}", e))?;
+            }
+            Ok(rules)
+        })
+    }
+    /// Every value of the config(except the profiles themselves) with where
+    /// it came from, by key.
+    pub fn sources(&self) -> Vec<(String, Value, Source)> {
+        let file: Map<String, Value> = std::fs::read_to_string(Config::path())
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        let overrides = self
+            .profile
+            .as_ref()
+            .and_then(|name| Some((name, self.profiles.get(name)?)));
+        let resolved = match serde_json::to_value(self) {
+            Ok(Value::Object(resolved)) => resolved,
+            _ => Map::new(),
+        };
+        resolved
+            .into_iter()
+            .filter(|(key, _)| key != "profiles")
+            .map(|(key, value)| {
+                let source = match overrides {
+                    Some((name, values)) if values.contains_key(&key) => {
+                        Source::Profile(name.to_owned())
+                    }
+                    _ if file.contains_key(&key) => Source::File,
+                    _ => Source::Default,
+                };
+                (key, value, source)
+            })
+            .collect()
+    }
+    /// Writes `value` as `key` to the config file, into the active profile if
+    /// it overrides `key`.
+    pub fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        let path = Config::path();
+        let err = |e: String| format!("Couldn't update {:?}: {}", path, e);
+        let mut file: Value = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+            .map_err(err)?;
+        let profile = self
+            .profile
+            .clone()
+            .filter(|name| file["profiles"][name.as_str()].get(key).is_some());
+        match profile {
+            Some(name) => file["profiles"][name.as_str()][key] = value,
+            None => file[key] = value,
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&file).unwrap() + "\n")
+            .map_err(|e| err(e.to_string()))
+    }
+    /// Replaces tag `old` with `new` in `tag_exec` and `tag_implications` of
+    /// the config file and all its profiles. Returns the sections which
+    /// changed, like "tag_exec" or "profiles.work.tag_implications".
+    pub fn rename_tag(old: &str, new: &str) -> Result<Vec<String>, String> {
+        let path = Config::path();
+        let err = |e: String| format!("Couldn't update {:?}: {}", path, e);
+        let mut file: Value = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+            .map_err(err)?;
+        let mut changed = Vec::new();
+        let mut sections: Vec<(String, &mut Map<String, Value>)> = Vec::new();
+        let (profiles, top) = match file.as_object_mut() {
+            Some(top) => (top.remove("profiles"), top),
+            None => return Err(err("not a JSON object".into())),
+        };
+        sections.push((String::new(), top));
+        let mut profiles = profiles.unwrap_or(Value::Null);
+        if let Some(profiles) = profiles.as_object_mut() {
+            for (name, profile) in profiles.iter_mut() {
+                if let Some(profile) = profile.as_object_mut() {
+                    sections.push((format!("profiles.{}.", name), profile));
+                }
+            }
+        }
+        for (prefix, section) in sections {
+            if let Some(Value::Object(exec)) = section.get_mut("tag_exec") {
+                if let Some(cmd) = exec.remove(old) {
+                    // an existing command of the new tag wins
+                    exec.entry(new).or_insert(cmd);
+                    changed.push(format!("{}tag_exec", prefix));
+                }
+            }
+            if let Some(Value::Object(rules)) = section.get_mut("tag_implications") {
+                let before = rules.clone();
+                if let Some(implied) = rules.remove(old) {
+                    let merged = rules.entry(new).or_insert(Value::Array(Vec::new()));
+                    if let (Value::Array(merged), Value::Array(implied)) = (merged, implied) {
+                        merged.extend(implied);
+                    }
+                }
+                for (tag, implied) in rules.iter_mut() {
+                    if let Value::Array(implied) = implied {
+                        let mut seen = HashSet::new();
+                        implied.retain_mut(|t| {
+                            if t == old {
+                                *t = Value::from(new);
+                            }
+                            t != tag.as_str() && seen.insert(t.clone())
+                        });
+                    }
+                }
+                if *rules != before {
+                    changed.push(format!("{}tag_implications", prefix));
+                }
+            }
+        }
+        if profiles.is_object() {
+            file["profiles"] = profiles;
+        }
+        if !changed.is_empty() {
+            std::fs::write(&path, serde_json::to_string_pretty(&file).unwrap() + "\n")
+                .map_err(|e| err(e.to_string()))?;
+        }
+        Ok(changed)
+    }
+    pub fn new(profile: Option<&str>) -> Config {
+        let path = Config::path();
+
+        let config_text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Couldn't open file {:?}:\n{}", &path, e));
+
+        let mut value: Value = serde_json::from_str(&config_text).unwrap();
+        let mut conf: Config = serde_json::from_value(value.clone()).unwrap();
+        if let Some(name) = profile {
+            let overrides = conf.profiles.get(name).unwrap_or_else(|| {
+                panic!(
+                    "Profile '{}' not found in {:?}(available: {})",
+                    name,
+                    &path,
+                    conf.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            });
+            let config = value.as_object_mut().unwrap();
+            for (key, val) in overrides {
+                config.insert(key.to_owned(), val.to_owned());
+            }
+            conf = serde_json::from_value(value).unwrap();
+        }
+        conf.profile = profile.map(|p| p.to_owned());
+        conf
+    }
+}
+
+/// Builder of a [`Config`] with the defaults of a config file only setting
+/// `dir`, see [`Config::builder`]. Values are checked by `build`.
+#[derive(Default)]
+pub struct ConfigBuilder {
+    values: Map<String, Value>,
+}
+
+impl ConfigBuilder {
+    /// Root directory containing projects, the only required value.
+    pub fn dir(self, dir: impl Into<String>) -> Self {
+        self.value("dir", Value::String(dir.into()))
+    }
+    pub fn exec(self, program: impl Into<String>) -> Self {
+        self.value("exec", Value::String(program.into()))
+    }
+    /// Program projects with `tag` are opened with, can be repeated.
+    pub fn tag_exec(mut self, tag: impl Into<String>, program: impl Into<String>) -> Self {
+        let tag_exec = self
+            .values
+            .entry("tag_exec")
+            .or_insert(Value::Object(Map::new()));
+        tag_exec[tag.into()] = Value::String(program.into());
+        self
+    }
+    pub fn shell(self, shell: impl Into<String>) -> Self {
+        self.value("shell", Value::String(shell.into()))
+    }
+    pub fn editor(self, editor: impl Into<String>) -> Self {
+        self.value("editor", Value::String(editor.into()))
+    }
+    pub fn relative_time(self, relative: bool) -> Self {
+        self.value("relative_time", Value::Bool(relative))
+    }
+    pub fn time_format(self, format: impl Into<String>) -> Self {
+        self.value("time_format", Value::String(format.into()))
+    }
+    pub fn normalize_tags(self, normalize: bool) -> Self {
+        self.value("normalize_tags", Value::Bool(normalize))
+    }
+    pub fn slugify_names(self, slugify: bool) -> Self {
+        self.value("slugify_names", Value::Bool(slugify))
+    }
+    pub fn name_pattern(self, pattern: impl Into<String>) -> Self {
+        self.value("name_pattern", Value::String(pattern.into()))
+    }
+    pub fn tag_pattern(self, pattern: impl Into<String>) -> Self {
+        self.value("tag_pattern", Value::String(pattern.into()))
+    }
+    /// Tags added along with `tag`, can be repeated.
+    pub fn tag_implication(mut self, tag: impl Into<String>, implied: Vec<String>) -> Self {
+        let implications = self
+            .values
+            .entry("tag_implications")
+            .or_insert(Value::Object(Map::new()));
+        implications[tag.into()] = Value::from(implied);
+        self
+    }
+    /// Days deleted projects are kept, `None` keeps them until emptied.
+    pub fn trash_keep_days(self, days: Option<u64>) -> Self {
+        self.value("trash_keep_days", Value::from(days))
+    }
+    pub fn backup_keep(self, keep: usize) -> Self {
+        self.value("backup_keep", Value::from(keep))
+    }
+    pub fn worktree_dir(self, dir: impl Into<String>) -> Self {
+        self.value("worktree_dir", Value::String(dir.into()))
+    }
+    /// Sets `key` like it would be in the config file, for values without
+    /// their own method.
+    pub fn value(mut self, key: &str, value: Value) -> Self {
+        self.values.insert(key.to_owned(), value);
+        self
+    }
+    /// Makes the config, failing if `dir` is missing or a value is invalid.
+    pub fn build(self) -> Result<Config, String> {
+        if !self.values.contains_key("dir") {
+            return Err("Config needs dir".into());
+        }
+        let conf: Config = serde_json::from_value(Value::Object(self.values))
+            .map_err(|e| format!("Invalid config: {}", e))?;
+        conf.name_pattern()?;
+        conf.tag_rules()?;
+        TimeStyle::new(conf.relative_time, &conf.time_format)?;
+        Ok(conf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(implications: &[(&str, &[&str])]) -> TagRules {
+        TagRules {
+            pattern: Regex::new(".*").unwrap(),
+            max_length: None,
+            implications: implications
+                .iter()
+                .map(|(tag, implied)| {
+                    (
+                        tag.to_string(),
+                        implied.iter().map(|t| t.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn tags(tags: &[&str]) -> HashSet<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
 
-        let config_text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Couldn't open file {:?}:\n{}", &path, e));
+    #[test]
+    fn implications_chain() {
+        let rules = rules(&[("actix", &["rust"]), ("rust", &["code"])]);
+        assert_eq!(
+            rules.imply(&tags(&[]), tags(&["actix"])),
+            tags(&["actix", "rust", "code"])
+        );
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let rules = rules(&[("a", &["b"]), ("b", &["a"])]);
+        assert_eq!(rules.imply(&tags(&[]), tags(&["a"])), tags(&["a", "b"]));
+        // adding another tag keeps the cycle, which implies itself
+        assert_eq!(
+            rules.imply(&tags(&["a", "b"]), tags(&["a", "b", "x"])),
+            tags(&["a", "b", "x"])
+        );
+    }
+
+    #[test]
+    fn implied_tags_are_dropped_with_their_source() {
+        let rules = rules(&[("actix", &["rust"]), ("rust", &["code"])]);
+        let old = tags(&["actix", "rust", "code", "web"]);
+        assert_eq!(
+            rules.imply(&old, tags(&["rust", "code", "web"])),
+            tags(&["web"])
+        );
+    }
 
-        serde_json::from_str(&config_text).unwrap()
+    #[test]
+    fn explicit_tags_are_kept() {
+        let rules = rules(&[("actix", &["rust"])]);
+        // rust was set by hand before actix implied it
+        let old = tags(&["rust", "web"]);
+        assert_eq!(
+            rules.imply(&old, tags(&["rust", "web", "cli"])),
+            tags(&["rust", "web", "cli"])
+        );
+        // rust was added by hand along with removing actix
+        let old = tags(&["actix", "web"]);
+        assert_eq!(
+            rules.imply(&old, tags(&["rust", "web"])),
+            tags(&["rust", "web"])
+        );
     }
 }
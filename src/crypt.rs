@@ -0,0 +1,46 @@
+//! Encryption of the sensitive parts of project metadata with external tools
+//! like age or gpg, using the commands of `crypt` in the config.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use crate::config::Crypt;
+
+/// Runs `cmd` with `input` on stdin, returning what it printed. The command
+/// can still ask for a passphrase on the terminal.
+fn pipe(cmd: &str, input: &str) -> Result<String, String> {
+    let mut parts = cmd.split_whitespace();
+    let program = parts.next().ok_or("Empty command in crypt of the config")?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run '{}': {}", cmd, e))?;
+    // written from another thread so a command printing before reading
+    // everything can't block on a full pipe
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Couldn't run '{}': {}", cmd, e))?;
+    writer
+        .join()
+        .unwrap()
+        .map_err(|e| format!("Couldn't write to '{}': {}", cmd, e))?;
+    if !output.status.success() {
+        return Err(format!("'{}' failed with {}", cmd, output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("'{}' didn't print text", cmd))
+}
+
+pub fn encrypt(crypt: &Crypt, text: &str) -> Result<String, String> {
+    pipe(&crypt.encrypt, text)
+}
+
+pub fn decrypt(crypt: &Crypt, text: &str) -> Result<String, String> {
+    pipe(&crypt.decrypt, text)
+}
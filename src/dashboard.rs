@@ -0,0 +1,93 @@
+//! Self-contained HTML page written by `report --html`: a sortable and
+//! filterable table of projects, a tag cloud and a summary of recent activity.
+//! Everything is inlined so the file can be shared on its own.
+
+use std::collections::HashMap;
+
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+use crate::{
+    format::{escape_html, Table},
+    project::Project,
+    stats,
+};
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;width:100%}\
+th,td{border-bottom:1px solid #ddd;padding:.3em .6em;text-align:left}\
+th{cursor:pointer;user-select:none;background:#f4f4f4}\
+input{padding:.3em;width:20em;margin-bottom:1em}\
+.tags span{display:inline-block;margin:.2em .4em}\
+pre{line-height:1.1}";
+
+// sorts by the clicked column(again to reverse) and hides rows not
+// containing the filter text
+const SCRIPT: &str = "const t=document.querySelector('table');\
+document.querySelectorAll('th').forEach((th,i)=>th.onclick=()=>{\
+const asc=th.dataset.asc!=='1';th.dataset.asc=asc?'1':'0';\
+const rows=[...t.tBodies[0].rows];\
+rows.sort((a,b)=>a.cells[i].textContent.localeCompare(b.cells[i].textContent,undefined,{numeric:true})*(asc?1:-1));\
+rows.forEach(r=>t.tBodies[0].appendChild(r));});\
+document.querySelector('input').oninput=e=>{const q=e.target.value.toLowerCase();\
+[...t.tBodies[0].rows].forEach(r=>r.hidden=!r.textContent.toLowerCase().includes(q));};";
+
+/// Tags sized by how many projects use them, alphabetically.
+fn tag_cloud(tags: &HashMap<String, usize>) -> String {
+    let max = tags.values().copied().max().unwrap_or(1);
+    let mut tags: Vec<(&String, &usize)> = tags.iter().collect();
+    tags.sort();
+    tags.iter()
+        .map(|(tag, count)| {
+            format!(
+                "<span style=\"font-size:{:.2}em\" title=\"{} project(s)\">{}</span>",
+                0.8 + 1.2 * **count as f64 / max as f64,
+                count,
+                escape_html(tag)
+            )
+        })
+        .collect()
+}
+
+/// Number of `projects` accessed in the last week, month and year, followed
+/// by the activity heatmap of `stats`.
+fn activity(projects: &[&Project], offset: UtcOffset) -> String {
+    let now = OffsetDateTime::now_utc();
+    let since = |days: i64| {
+        projects
+            .iter()
+            .filter(|p| now - p.get_accessed() <= Duration::days(days))
+            .count()
+    };
+    format!(
+        "<p>Accessed in the last week: {}, month: {}, year: {} (of {})</p><pre>{}</pre>",
+        since(7),
+        since(30),
+        since(365),
+        projects.len(),
+        escape_html(&stats::heatmap(
+            &stats::activity(projects.iter().copied(), offset),
+            26,
+            offset
+        ))
+    )
+}
+
+pub fn render(
+    table: &Table,
+    projects: &[&Project],
+    tags: &HashMap<String, usize>,
+    offset: UtcOffset,
+) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Projects</title>\
+        <style>{}</style></head><body>\n<h1>Projects</h1>\n\
+        <h2>Activity</h2>\n{}\n<h2>Tags</h2>\n<div class=\"tags\">{}</div>\n\
+        <h2>All projects</h2>\n<input placeholder=\"Filter\u{2026}\">\n{}\
+        <script>{}</script>\n</body></html>\n",
+        STYLE,
+        activity(projects, offset),
+        tag_cloud(tags),
+        table.to_html(),
+        SCRIPT
+    )
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    config::Cleaner,
+    index::{self, Size},
+};
+
+/// Returns the total size in bytes of every file under `path`.
+/// Symlinks are not followed so linked directories aren't counted twice.
+pub fn dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    let mut size = 0;
+    for entry in entries.flatten() {
+        let meta = match entry.path().symlink_metadata() {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        if meta.is_dir() {
+            size += dir_size(&entry.path());
+        } else {
+            size += meta.len();
+        }
+    }
+    size
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Disk usage of projects keyed by their directory, kept in the project
+/// index between runs because walking big `target/` or `node_modules/`
+/// directories is slow. A size is measured again when the modification time
+/// of the directory changed.
+pub struct SizeCache {
+    sizes: HashMap<PathBuf, Size>,
+    changed: HashMap<PathBuf, Option<Size>>,
+}
+
+impl SizeCache {
+    pub fn load() -> Self {
+        SizeCache {
+            sizes: index::sizes(),
+            changed: HashMap::new(),
+        }
+    }
+    /// Returns the cached size of `dir`, measuring it first if it's not cached,
+    /// the directory was modified since or `refresh` is set.
+    pub fn get(&mut self, dir: &Path, refresh: bool) -> u64 {
+        let key = key(dir);
+        let mtime = mtime(dir);
+        if let Some(size) = self.sizes.get(&key) {
+            if !refresh && size.mtime == mtime {
+                return size.bytes;
+            }
+        }
+        let size = Size {
+            bytes: dir_size(dir),
+            mtime,
+        };
+        self.sizes.insert(key.clone(), size);
+        self.changed.insert(key, Some(size));
+        size.bytes
+    }
+    pub fn invalidate(&mut self, dir: &Path) {
+        let key = key(dir);
+        self.sizes.remove(&key);
+        self.changed.insert(key, None);
+    }
+    pub fn save(&self) -> Result<(), String> {
+        match self.changed.is_empty() {
+            true => Ok(()),
+            false => index::save_sizes(&self.changed),
+        }
+    }
+}
+
+/// Directories are indexed canonicalized.
+fn key(dir: &Path) -> PathBuf {
+    dir.canonicalize().unwrap_or_else(|_| dir.to_owned())
+}
+
+fn mtime(dir: &Path) -> i64 {
+    fs::metadata(dir)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Applies every cleaner whose marker file exists in `dir`.
+/// Returns true if at least one cleaner matched.
+pub fn clean(dir: &Path, cleaners: &[Cleaner]) -> Result<bool, String> {
+    let mut matched = false;
+    for cleaner in cleaners.iter().filter(|c| dir.join(&c.marker).exists()) {
+        matched = true;
+        if let Some(cmd) = &cleaner.command {
+            let cmd: Vec<&str> = cmd.split(' ').collect();
+            let status = Command::new(cmd[0])
+                .args(&cmd[1..])
+                .current_dir(dir)
+                .status()
+                .map_err(|e| format!("Couldn't run '{}' in {:?}: {}", cmd.join(" "), dir, e))?;
+            if !status.success() {
+                return Err(format!("'{}' failed in {:?}", cmd.join(" "), dir));
+            }
+        }
+        for target in &cleaner.remove {
+            let target = dir.join(target);
+            if target.is_dir() {
+                fs::remove_dir_all(&target)
+                    .map_err(|e| format!("Couldn't remove {:?}: {}", target, e))?;
+            }
+        }
+    }
+    Ok(matched)
+}
@@ -0,0 +1,173 @@
+use std::{fmt::Display, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectErrorTypes {
+    NonExistingProject,
+    /// Metadata file which couldn't be read or parsed while loading
+    BrokenMetadata,
+    /// Name already used by another project, or a directory in the way of
+    /// a renamed project
+    DuplicateName,
+    /// Names which are the same on case-insensitive filesystems, see
+    /// [`crate::names::fold`]
+    NameCollision,
+    /// Path which the current user can't write to
+    Permission,
+    /// Change to a project marked as locked
+    Locked,
+    /// Root which is a project itself or inside one
+    NestedRoot,
+    Other,
+}
+
+/// Error of project operations which callers can inspect instead of only
+/// showing it. Converts to and from the plain `String` errors used elsewhere.
+#[derive(Debug)]
+pub struct ProjectError {
+    pub kind: ProjectErrorTypes,
+    pub message: String,
+    /// Similar project names when a lookup failed, most similar first
+    pub suggestions: Vec<String>,
+}
+
+impl ProjectError {
+    pub fn new(kind: ProjectErrorTypes, message: String) -> Self {
+        ProjectError {
+            kind,
+            message,
+            suggestions: Vec::new(),
+        }
+    }
+    /// Error for `path` not being writable, found before changing anything.
+    pub fn permission(path: &Path) -> Self {
+        ProjectError::new(
+            ProjectErrorTypes::Permission,
+            format!(
+                "permission denied on {} — try `chmod u+w {}` or running as its owner",
+                path.display(),
+                path.display()
+            ),
+        )
+    }
+    /// Error for `name` being the same as the name of project `other` on
+    /// case-insensitive filesystems.
+    pub fn name_collision(name: &str, other: &str) -> Self {
+        ProjectError::new(
+            ProjectErrorTypes::NameCollision,
+            format!(
+                "'{}' collides with project '{}' on case-insensitive filesystems",
+                name, other
+            ),
+        )
+    }
+    /// Error for the directory of project `name` having disappeared since it
+    /// was loaded.
+    pub fn missing_dir(name: &str, path: &Path) -> Self {
+        ProjectError::new(
+            ProjectErrorTypes::NonExistingProject,
+            format!(
+                "Directory of '{}'({}) doesn't exist anymore, it was moved or deleted",
+                name,
+                path.display()
+            ),
+        )
+    }
+    /// Error for changing project `name` while it's locked.
+    pub fn locked(name: &str) -> Self {
+        ProjectError::new(
+            ProjectErrorTypes::Locked,
+            format!("'{}' is locked, pass --unlock to change it anyway", name),
+        )
+    }
+    /// Error for a missing project, suggesting up to three known names which
+    /// are a few edits away from `name`.
+    pub fn non_existing<'a>(name: &str, known: impl Iterator<Item = &'a String>) -> Self {
+        let max_distance = (name.chars().count() / 3).max(2);
+        let mut candidates: Vec<(usize, &String)> = known
+            .map(|k| (edit_distance(&name.to_lowercase(), &k.to_lowercase()), k))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        candidates.sort();
+        ProjectError {
+            kind: ProjectErrorTypes::NonExistingProject,
+            message: format!("Such project({}) doesn't exist", name),
+            suggestions: candidates
+                .into_iter()
+                .take(3)
+                .map(|(_, k)| k.to_owned())
+                .collect(),
+        }
+    }
+}
+
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.suggestions.is_empty() {
+            write!(f, "\nDid you mean: {}?", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How bad a problem found while loading projects is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Everything was loaded but something needs attention, like names
+    /// colliding on case-insensitive filesystems
+    Warning,
+    /// A project couldn't be loaded and is missing from the manager
+    Error,
+}
+
+/// Problem found while loading projects, passed to the listener of
+/// [`crate::project::ProjectManager::load_with_events`] as soon as it's found
+/// and kept in [`crate::project::ProjectManager::load_errors`].
+#[derive(Debug)]
+pub struct LoadProblem {
+    pub severity: Severity,
+    pub error: ProjectError,
+}
+
+impl Display for LoadProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl From<String> for ProjectError {
+    fn from(message: String) -> Self {
+        ProjectError {
+            kind: ProjectErrorTypes::Other,
+            message,
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+impl From<ProjectError> for String {
+    fn from(e: ProjectError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Reports a problem which doesn't stop the command on stderr, so it never
+/// mixes with output meant for pipes.
+pub fn warn(message: impl Display) {
+    eprintln!("warning: {}", message);
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
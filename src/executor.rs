@@ -0,0 +1,38 @@
+//! Picks the program `exec` runs in a project when no command is given.
+
+use std::collections::HashSet;
+
+use crate::config::Config;
+
+/// Shell of the user: $SHELL, then `configured`(the `shell` value of the
+/// config) and then the system shell.
+pub fn shell(configured: Option<&str>) -> String {
+    if let Some(shell) = std::env::var("SHELL").ok().filter(|s| !s.is_empty()) {
+        return shell;
+    }
+    if let Some(shell) = configured.filter(|s| !s.is_empty()) {
+        return shell.to_owned();
+    }
+    if cfg!(windows) {
+        std::env::var("ComSpec").unwrap_or_else(|_| "cmd.exe".into())
+    } else {
+        "/bin/sh".into()
+    }
+}
+
+/// Program a project with `tags` is opened with: the `tag_exec` entry of its
+/// first tag(alphabetically) having one, `exec` of the config if it's set,
+/// otherwise the shell of the user.
+pub fn program(conf: &Config, tags: &HashSet<String>) -> String {
+    if let Some(exec) = conf
+        .tag_exec
+        .iter()
+        .find_map(|(tag, exec)| tags.contains(tag).then_some(exec))
+    {
+        return exec.to_owned();
+    }
+    match &conf.exec {
+        Some(exec) => exec.to_owned(),
+        None => shell(conf.shell.as_deref()),
+    }
+}
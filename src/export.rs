@@ -0,0 +1,119 @@
+//! Markdown notes about projects for knowledge bases like Obsidian: one note
+//! per project with YAML front matter plus an index linking all of them.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{format, git, project::Project};
+
+/// Front matter value marking notes written by `export`, so notes of removed
+/// projects can be cleaned up without touching anything else.
+const SOURCE: &str = "source: cli-project-manager";
+const INDEX_FILE: &str = "index.md";
+
+#[derive(Clone, Copy)]
+pub enum Links {
+    /// `[[name]]`, resolved by Obsidian
+    Wiki,
+    /// `[name](name.md)`
+    Markdown,
+}
+
+impl Links {
+    fn to(&self, name: &str) -> String {
+        match self {
+            Links::Wiki => format!("[[{}]]", name),
+            Links::Markdown => format!("[{}]({}.md)", name, name),
+        }
+    }
+}
+
+/// Note about `project` living at `path`.
+pub fn note(project: &Project, path: &Path) -> String {
+    // JSON strings and arrays are valid YAML, which saves quoting by hand
+    let time = |t: OffsetDateTime| t.format(&Rfc3339).unwrap_or_default();
+    let mut tags: Vec<String> = project.get_tags().into_iter().collect();
+    tags.sort();
+    let repo = git::remote_url(path);
+    let mut res = format!(
+        "---\n{}\nname: {}\ntags: {}\ncreated: {}\naccessed: {}\npath: {}\n",
+        SOURCE,
+        json!(project.get_name()),
+        json!(tags),
+        time(project.get_created()),
+        time(project.get_accessed()),
+        json!(path),
+    );
+    if let Some(title) = project.get_title() {
+        res += &format!("title: {}\n", json!(title));
+    }
+    if let Some(due) = project.get_due() {
+        res += &format!("due: {}\n", format::format_date(due));
+    }
+    if let Some(priority) = project.get_priority() {
+        res += &format!("priority: {}\n", priority);
+    }
+    if let Some(repo) = &repo {
+        res += &format!("repo: {}\n", json!(repo));
+    }
+    res += &format!(
+        "---\n\n# {}\n\nPath: [{}](file://{})\n",
+        project.get_title().unwrap_or(project.get_name()),
+        path.display(),
+        path.display().to_string().replace(' ', "%20")
+    );
+    if let Some(repo) = repo {
+        res += &format!("Repository: {}\n", repo);
+    }
+    res
+}
+
+/// Index linking every project, with its tags.
+pub fn index(projects: &[&Project], links: Links) -> String {
+    let mut res = String::from("# Projects\n\n");
+    for project in projects {
+        let mut tags: Vec<String> = project.get_tags().into_iter().collect();
+        tags.sort();
+        res += &format!("- {}", links.to(project.get_name()));
+        if !tags.is_empty() {
+            res += &format!(" ({})", tags.join(", "));
+        }
+        res += "\n";
+    }
+    res
+}
+
+/// Writes notes of `projects`(with their paths) and the index into `dir`,
+/// removing notes of projects which don't exist anymore. Returns the number
+/// of removed notes.
+pub fn write(dir: &Path, projects: &[(&Project, PathBuf)], links: Links) -> Result<usize, String> {
+    let err = |path: &Path, e: std::io::Error| format!("Couldn't write {:?}: {}", path, e);
+    fs::create_dir_all(dir).map_err(|e| err(dir, e))?;
+    let mut removed = 0;
+    for entry in fs::read_dir(dir).map_err(|e| err(dir, e))?.flatten() {
+        let path = entry.path();
+        let stale = path.extension().is_some_and(|e| e == "md")
+            && !projects
+                .iter()
+                .any(|(p, _)| path.file_stem().is_some_and(|s| s == p.get_name().as_str()))
+            && fs::read_to_string(&path)
+                .is_ok_and(|data| data.starts_with(&format!("---\n{}\n", SOURCE)));
+        if stale {
+            fs::remove_file(&path).map_err(|e| err(&path, e))?;
+            removed += 1;
+        }
+    }
+    for (project, path) in projects {
+        let file = dir.join(format!("{}.md", project.get_name()));
+        fs::write(&file, note(project, path)).map_err(|e| err(&file, e))?;
+    }
+    let file = dir.join(INDEX_FILE);
+    let list: Vec<&Project> = projects.iter().map(|(p, _)| *p).collect();
+    fs::write(&file, index(&list, links)).map_err(|e| err(&file, e))?;
+    Ok(removed)
+}
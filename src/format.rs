@@ -0,0 +1,602 @@
+use std::{
+    fmt::Display,
+    io::IsTerminal,
+    path::Path,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde_json::Value;
+use time::{
+    format_description::{self, well_known::Rfc3339, FormatItem, OwnedFormatItem},
+    macros::format_description,
+    Date, Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    disk::{self, SizeCache},
+    git,
+    project::{self, Project},
+};
+
+const DATE_FORMAT: &[FormatItem] = format_description!("[year]-[month]-[day]");
+const DATETIME_FORMAT: &[FormatItem] = format_description!("[year]-[month]-[day] [hour]:[minute]");
+const PORCELAIN_TIME_FORMAT: &str = "[year]-[month]-[day]T[hour]:[minute]:[second]Z";
+
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    Csv,
+    Markdown,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "md" | "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(format!("Unknown format '{}'(expected csv or md)", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Column {
+    Name,
+    Tags,
+    Created,
+    Accessed,
+    Size,
+    Status,
+    Due,
+    Owner,
+}
+
+impl FromStr for Column {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "name" => Ok(Column::Name),
+            "tags" => Ok(Column::Tags),
+            "created" => Ok(Column::Created),
+            "accessed" => Ok(Column::Accessed),
+            "size" => Ok(Column::Size),
+            "status" => Ok(Column::Status),
+            "due" => Ok(Column::Due),
+            "owner" => Ok(Column::Owner),
+            other => Err(format!(
+                "Unknown column '{}'(expected name, tags, created, accessed, size, status, due or owner)",
+                other
+            )),
+        }
+    }
+}
+
+impl Column {
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Tags => "tags",
+            Column::Created => "created",
+            Column::Accessed => "accessed",
+            Column::Size => "size",
+            Column::Status => "status",
+            Column::Due => "due",
+            Column::Owner => "owner",
+        }
+    }
+    pub fn cell(
+        &self,
+        project: &Project,
+        path: &Path,
+        cache: &mut SizeCache,
+        style: &TimeStyle,
+    ) -> String {
+        match self {
+            Column::Name => project.get_name().to_owned(),
+            Column::Tags => {
+                let mut tags = project.get_tags().into_iter().collect::<Vec<_>>();
+                tags.sort();
+                tags.join(" ")
+            }
+            Column::Created => style.display(project.get_created()),
+            Column::Accessed => style.display(project.get_accessed()),
+            Column::Size => disk::format_size(cache.get(path, false)),
+            Column::Status => git::status(path).to_string(),
+            Column::Due => project.get_due().map(format_date).unwrap_or_default(),
+            Column::Owner => project.get_owner().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// A value of projects printed by `list --fields`, `custom.<key>` being a
+/// custom value from their metadata.
+#[derive(Clone)]
+pub enum Field {
+    Id,
+    Name,
+    Title,
+    Path,
+    Tags,
+    Created,
+    Accessed,
+    Modified,
+    Completed,
+    Due,
+    Priority,
+    Owner,
+    Custom(String),
+}
+
+impl FromStr for Field {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "id" => Ok(Field::Id),
+            "name" => Ok(Field::Name),
+            "title" => Ok(Field::Title),
+            "path" => Ok(Field::Path),
+            "tags" => Ok(Field::Tags),
+            "created" => Ok(Field::Created),
+            "accessed" => Ok(Field::Accessed),
+            "modified" => Ok(Field::Modified),
+            "completed" => Ok(Field::Completed),
+            "due" => Ok(Field::Due),
+            "priority" => Ok(Field::Priority),
+            "owner" => Ok(Field::Owner),
+            other => match other.strip_prefix("custom.") {
+                Some(key) if !key.is_empty() => Ok(Field::Custom(key.to_owned())),
+                _ => Err(format!(
+                    "Unknown field '{}'(expected id, name, title, path, tags, created, accessed, modified, completed, due, priority, owner or custom.<key>)",
+                    other
+                )),
+            },
+        }
+    }
+}
+
+impl Field {
+    /// Name of the field as given to `--fields`.
+    pub fn key(&self) -> String {
+        match self {
+            Field::Id => "id".into(),
+            Field::Name => "name".into(),
+            Field::Title => "title".into(),
+            Field::Path => "path".into(),
+            Field::Tags => "tags".into(),
+            Field::Created => "created".into(),
+            Field::Accessed => "accessed".into(),
+            Field::Modified => "modified".into(),
+            Field::Completed => "completed".into(),
+            Field::Due => "due".into(),
+            Field::Priority => "priority".into(),
+            Field::Owner => "owner".into(),
+            Field::Custom(key) => format!("custom.{}", key),
+        }
+    }
+    /// Value of the field for scripts: times are RFC 3339, tags are comma
+    /// separated and missing values are empty.
+    pub fn value(&self, project: &Project, path: &Path) -> String {
+        let time = |t: OffsetDateTime| t.format(&Rfc3339).unwrap_or_default();
+        match self {
+            Field::Id => project.get_id().cloned().unwrap_or_default(),
+            Field::Name => project.get_name().to_owned(),
+            Field::Title => project.get_title().cloned().unwrap_or_default(),
+            Field::Path => path.to_string_lossy().into_owned(),
+            Field::Tags => {
+                let mut tags = project.get_tags().into_iter().collect::<Vec<_>>();
+                tags.sort();
+                tags.join(",")
+            }
+            Field::Created => time(project.get_created()),
+            Field::Accessed => time(project.get_accessed()),
+            Field::Modified => time(project.get_modified()),
+            Field::Completed => project.get_completed().map(format_date).unwrap_or_default(),
+            Field::Due => project.get_due().map(format_date).unwrap_or_default(),
+            Field::Priority => project
+                .get_priority()
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            Field::Owner => project.get_owner().cloned().unwrap_or_default(),
+            Field::Custom(key) => match project.get_custom(key) {
+                None => String::new(),
+                Some(Value::String(s)) => s.to_owned(),
+                Some(value) => value.to_string(),
+            },
+        }
+    }
+}
+
+/// Columns `text` takes in a terminal, CJK characters and most emoji
+/// taking two.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// `text` followed by spaces so it takes `width` columns.
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+/// `text` cut to at most `width` columns, ending with "…" if it was cut.
+/// Wide characters are never split in half.
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_owned();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut res = String::new();
+    for c in text.chars() {
+        res.push(c);
+        // Measured on the whole prefix as emoji sequences aren't the sum of
+        // their characters
+        if res.width() + 1 > width {
+            res.pop();
+            break;
+        }
+    }
+    res + "…"
+}
+
+/// Projects due in this many days or less are highlighted.
+pub const DUE_SOON_DAYS: i64 = 7;
+
+pub fn format_date(date: Date) -> String {
+    date.format(DATE_FORMAT).unwrap_or_default()
+}
+
+/// Parses dates like "2025-07-01".
+pub fn parse_date(s: &str) -> Result<Date, String> {
+    Date::parse(s, DATE_FORMAT)
+        .map_err(|e| format!("Invalid date '{}'(expected YYYY-MM-DD): {}", s, e))
+}
+
+/// Parses times like "2025-07-01T14:30:00Z"(RFC 3339), "2025-07-01 14:30" or
+/// "2025-07-01", the last two in the timezone of `offset`.
+pub fn parse_datetime(s: &str, offset: UtcOffset) -> Result<OffsetDateTime, String> {
+    if let Ok(time) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(time.to_offset(UtcOffset::UTC));
+    }
+    let local = PrimitiveDateTime::parse(s, DATETIME_FORMAT)
+        .or_else(|_| Date::parse(s, DATE_FORMAT).map(|d| d.midnight()));
+    local
+        .map(|t| t.assume_offset(offset).to_offset(UtcOffset::UTC))
+        .map_err(|_| {
+            format!(
+                "Invalid time '{}'(expected YYYY-MM-DD, YYYY-MM-DD HH:MM or RFC 3339)",
+                s
+            )
+        })
+}
+
+/// Parses durations like "12h", "3d", "1w", "2m"(30 days) or "1y".
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let err = || {
+        format!(
+            "Invalid duration '{}'(expected a number and h, d, w, m or y)",
+            s
+        )
+    };
+    let split = s
+        .len()
+        .checked_sub(1)
+        .filter(|i| s.is_char_boundary(*i))
+        .ok_or_else(err)?;
+    let amount: i64 = s[..split].parse().map_err(|_| err())?;
+    match &s[split..] {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        "m" => Ok(Duration::days(amount * 30)),
+        "y" => Ok(Duration::days(amount * 365)),
+        _ => Err(err()),
+    }
+}
+
+/// Switches to the output of `--porcelain`, which doesn't change between
+/// versions: no colors, progress bars, truncation or relative dates.
+pub fn set_porcelain() {
+    PORCELAIN.store(true, Ordering::Relaxed);
+}
+
+pub fn porcelain() -> bool {
+    PORCELAIN.load(Ordering::Relaxed)
+}
+
+/// Whether output is read by a person: stdout is a terminal and
+/// `--porcelain` isn't set.
+pub fn interactive() -> bool {
+    std::io::stdout().is_terminal() && !porcelain()
+}
+
+/// Whether output can be colored: it's [`interactive`] and NO_COLOR isn't set.
+pub fn use_color() -> bool {
+    interactive() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Like "due 2025-07-01 (in 3 days)", in red when overdue and yellow when due
+/// soon if `color` is set. Only "due 2025-07-01" with `--porcelain`.
+pub fn due_text(due: Date, today: Date, color: bool) -> String {
+    if porcelain() {
+        return format!("due {}", format_date(due));
+    }
+    let days = (due - today).whole_days();
+    let when = match days {
+        0 => "today".to_owned(),
+        1 => "tomorrow".to_owned(),
+        _ if days < 0 => format!(
+            "overdue by {} day{}",
+            -days,
+            if days == -1 { "" } else { "s" }
+        ),
+        _ => format!("in {} days", days),
+    };
+    let text = format!("due {} ({})", format_date(due), when);
+    if color && days < 0 {
+        format!("\x1b[31m{}\x1b[0m", text)
+    } else if color && days <= DUE_SOON_DAYS {
+        format!("\x1b[33m{}\x1b[0m", text)
+    } else {
+        text
+    }
+}
+
+/// Describes `time` relative to now, like "3 days ago" or "in 2 hours".
+pub fn relative_time(time: OffsetDateTime) -> String {
+    let diff = OffsetDateTime::now_utc() - time;
+    let seconds = diff.whole_seconds().abs();
+    let (amount, unit) = match seconds {
+        0..=59 => return "just now".to_owned(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86_399 => (seconds / 3600, "hour"),
+        86_400..=2_591_999 => (seconds / 86_400, "day"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if diff.is_negative() {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+/// Settings for showing timestamps. Times are stored in UTC and converted to
+/// the local offset only for display.
+#[derive(Clone)]
+pub struct TimeStyle {
+    relative: bool,
+    offset: UtcOffset,
+    format: OwnedFormatItem,
+}
+
+impl TimeStyle {
+    pub fn new(relative: bool, format: &str) -> Result<Self, String> {
+        let format = format_description::parse_owned::<2>(format)
+            .map_err(|e| format!("Invalid time_format '{}': {}", format, e))?;
+        Ok(TimeStyle {
+            relative,
+            // fails when the offset can't be determined soundly(e.g. other
+            // threads are running), in which case UTC is the best we can do
+            offset: UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC),
+            format,
+        })
+    }
+    /// Absolute UTC times like "2025-07-01T12:00:00Z", for `--porcelain`.
+    pub fn porcelain() -> Self {
+        TimeStyle {
+            relative: false,
+            offset: UtcOffset::UTC,
+            format: format_description::parse_owned::<2>(PORCELAIN_TIME_FORMAT).unwrap(),
+        }
+    }
+    pub fn with_relative(mut self, relative: bool) -> Self {
+        self.relative = relative;
+        self
+    }
+    pub fn offset(&self) -> UtcOffset {
+        self.offset
+    }
+    pub fn today(&self) -> Date {
+        OffsetDateTime::now_utc().to_offset(self.offset).date()
+    }
+    pub fn absolute(&self, time: OffsetDateTime) -> String {
+        time.to_offset(self.offset)
+            .format(&self.format)
+            .unwrap_or_else(|e| e.to_string())
+    }
+    pub fn display(&self, time: OffsetDateTime) -> String {
+        if self.relative {
+            relative_time(time)
+        } else {
+            self.absolute(time)
+        }
+    }
+}
+
+/// Columns the name and tags of a project take at most in interactive lists.
+const ENTRY_WIDTH: usize = 60;
+
+/// How a project is shown in interactive lists: name, tags, when it was
+/// last accessed and its owner unless that's the current user.
+pub struct ProjectEntry<'a> {
+    pub project: Project,
+    style: &'a TimeStyle,
+    group: Option<String>,
+}
+
+impl<'a> ProjectEntry<'a> {
+    pub fn new(project: Project, style: &'a TimeStyle) -> Self {
+        ProjectEntry {
+            project,
+            style,
+            group: None,
+        }
+    }
+    /// Shows `label` in a column before the project, used as the section
+    /// header of grouped lists(empty for all but the first of a group).
+    pub fn grouped(mut self, label: String) -> Self {
+        self.group = Some(label);
+        self
+    }
+}
+
+impl Display for ProjectEntry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(group) = &self.group {
+            write!(f, "{} │ ", group)?;
+        }
+        write!(
+            f,
+            "{} ({})",
+            match porcelain() {
+                true => self.project.to_string(),
+                false => truncate(&self.project.to_string(), ENTRY_WIDTH),
+            },
+            self.style.display(self.project.get_accessed())
+        )?;
+        if let Some(due) = self.project.get_due() {
+            write!(f, " {}", due_text(due, self.style.today(), use_color()))?;
+        }
+        if let Some(owner) = self
+            .project
+            .get_owner()
+            .filter(|o| Some(*o) != project::current_user().as_ref())
+        {
+            write!(f, " @{}", owner)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits `projects` into sections by their first tag(alphabetically),
+/// keeping their order inside each section. Sections are sorted by tag with
+/// untagged projects last, under an empty tag.
+pub fn group_by_tag(projects: Vec<Project>) -> Vec<(String, Vec<Project>)> {
+    let mut groups: Vec<(String, Vec<Project>)> = Vec::new();
+    for project in projects {
+        let tag = project.get_tags().into_iter().min().unwrap_or_default();
+        match groups.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, group)) => group.push(project),
+            None => groups.push((tag, vec![project])),
+        }
+    }
+    groups.sort_by(|(a, _), (b, _)| (a.is_empty(), a).cmp(&(b.is_empty(), b)));
+    groups
+}
+
+/// Escapes `text` for use in HTML text and attribute values.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<String>) -> Self {
+        Table {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+    pub fn render(&self, format: &OutputFormat) -> String {
+        match format {
+            OutputFormat::Csv => self.to_csv(),
+            OutputFormat::Markdown => self.to_markdown(),
+        }
+    }
+    fn to_csv(&self) -> String {
+        let escape = |cell: &String| {
+            if cell.contains([',', '"', '\n']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_owned()
+            }
+        };
+        std::iter::once(&self.headers)
+            .chain(&self.rows)
+            .map(|row| row.iter().map(escape).collect::<Vec<_>>().join(",") + "\n")
+            .collect()
+    }
+    pub fn to_html(&self) -> String {
+        let line = |row: &Vec<String>, cell: &str| {
+            row.iter()
+                .map(|c| format!("<{}>{}</{}>", cell, escape_html(c), cell))
+                .collect::<String>()
+        };
+        let mut res = format!(
+            "<table>\n<thead><tr>{}</tr></thead>\n<tbody>\n",
+            line(&self.headers, "th")
+        );
+        for row in &self.rows {
+            res += &format!("<tr>{}</tr>\n", line(row, "td"));
+        }
+        res + "</tbody>\n</table>\n"
+    }
+    fn to_markdown(&self) -> String {
+        let line = |row: &Vec<String>| {
+            format!(
+                "| {} |\n",
+                row.iter()
+                    .map(|c| c.replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            )
+        };
+        let mut res = line(&self.headers);
+        res += &line(&self.headers.iter().map(|_| "---".to_owned()).collect());
+        for row in &self.rows {
+            res += &line(row);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pad_aligns_wide_characters_by_columns() {
+        assert_eq!(pad("日本", 6), "日本  ");
+        assert_eq!(pad("🦀x", 4), "🦀x ");
+        assert_eq!(
+            display_width(&pad("日本", 6)),
+            display_width(&pad("abc", 6))
+        );
+    }
+
+    #[test]
+    fn truncate_keeps_wide_characters_whole() {
+        assert_eq!(truncate("日本語の名前", 6), "日本…");
+        assert_eq!(truncate("日本語の名前", 5), "日本…");
+        assert_eq!(truncate("🦀🦀🦀", 4), "🦀…");
+        assert_eq!(truncate("日本", 4), "日本");
+    }
+
+    #[test]
+    fn truncate_fits_the_width() {
+        for text in ["project", "日本語の名前", "🦀 crab", "👩‍💻 laptop"] {
+            for width in 0..10 {
+                let cut = truncate(text, width);
+                assert!(display_width(&cut) <= width, "{:?} in {}", cut, width);
+            }
+        }
+        assert_eq!(truncate("project", 0), "");
+        assert_eq!(truncate("日本", 1), "…");
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt::Display, path::Path, process::Command};
+
+pub enum GitStatus {
+    NotRepo,
+    Clean,
+    Uncommitted,
+    Unpushed,
+}
+
+impl Display for GitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            GitStatus::NotRepo => "-",
+            GitStatus::Clean => "clean",
+            GitStatus::Uncommitted => "uncommitted",
+            GitStatus::Unpushed => "unpushed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+fn git(path: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// URL of the `origin` remote of the repository at `path`, if it has one.
+pub fn remote_url(path: &Path) -> Option<String> {
+    if !path.join(".git").exists() {
+        return None;
+    }
+    git(path, &["config", "--get", "remote.origin.url"]).map(|url| url.trim().to_owned())
+}
+
+/// Checks out `branch` of the repository at `repo` into a new worktree at
+/// `path`, creating the branch from HEAD if it doesn't exist yet.
+pub fn add_worktree(repo: &Path, branch: &str, path: &Path) -> Result<(), String> {
+    let path = path.to_string_lossy();
+    let exists = git(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch),
+        ],
+    )
+    .is_some();
+    let args = match exists {
+        true => vec!["worktree", "add", &path, branch],
+        false => vec!["worktree", "add", "-b", branch, &path],
+    };
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .map_err(|e| format!("Couldn't run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git worktree add failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Reports whether the repository at `path` has uncommitted changes or
+/// commits that aren't on any remote. Uncommitted changes take precedence.
+/// Directories that aren't git repositories(or when git isn't installed)
+/// are reported as `NotRepo`.
+pub fn status(path: &Path) -> GitStatus {
+    if !path.join(".git").exists() {
+        return GitStatus::NotRepo;
+    }
+    match git(path, &["status", "--porcelain"]) {
+        None => return GitStatus::NotRepo,
+        Some(out) if !out.trim().is_empty() => return GitStatus::Uncommitted,
+        _ => {}
+    }
+    match git(
+        path,
+        &["log", "--branches", "--not", "--remotes", "--oneline"],
+    ) {
+        Some(out) if !out.trim().is_empty() => GitStatus::Unpushed,
+        _ => GitStatus::Clean,
+    }
+}
@@ -0,0 +1,137 @@
+//! Append-only journal of commands which changed projects, one JSON object
+//! per line so a damaged entry doesn't lose the rest.
+
+use std::{collections::HashSet, fmt::Display, fs, io::Write, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::paths;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub command: String,
+    pub project: String,
+    pub old: String,
+    pub new: String,
+    /// Tags added and removed, for changes of tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<TagDiff>,
+}
+
+/// Tags added and removed by a change, both sorted.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct TagDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl TagDiff {
+    pub fn new(old: &HashSet<String>, new: &HashSet<String>) -> Self {
+        let mut added: Vec<String> = new.difference(old).cloned().collect();
+        let mut removed: Vec<String> = old.difference(new).cloned().collect();
+        added.sort();
+        removed.sort();
+        TagDiff { added, removed }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Like "+cli -python".
+impl Display for TagDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let added = self.added.iter().map(|t| format!("+{}", t));
+        let removed = self.removed.iter().map(|t| format!("-{}", t));
+        write!(f, "{}", added.chain(removed).collect::<Vec<_>>().join("  "))
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    paths::state(HISTORY_FILE)
+}
+
+/// Sorted, comma separated tags for recording tag changes.
+pub fn tags(tags: &HashSet<String>) -> String {
+    let mut tags: Vec<&String> = tags.iter().collect();
+    tags.sort();
+    tags.iter()
+        .map(|t| t.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Appends an entry to history. Failing to record isn't worth failing the
+/// command that already succeeded, so errors are only reported.
+pub fn record(command: &str, project: &str, old: &str, new: &str) {
+    append(Entry {
+        time: OffsetDateTime::now_utc(),
+        command: command.to_owned(),
+        project: project.to_owned(),
+        old: old.to_owned(),
+        new: new.to_owned(),
+        diff: None,
+    });
+}
+
+/// Records a change of the tags of `project` from `old` to `new` along with
+/// its [`TagDiff`], which is returned. Nothing is recorded if they're the same.
+pub fn record_tags(
+    command: &str,
+    project: &str,
+    old: &HashSet<String>,
+    new: &HashSet<String>,
+) -> TagDiff {
+    let diff = TagDiff::new(old, new);
+    if diff.is_empty() {
+        return diff;
+    }
+    append(Entry {
+        time: OffsetDateTime::now_utc(),
+        command: command.to_owned(),
+        project: project.to_owned(),
+        old: tags(old),
+        new: tags(new),
+        diff: Some(diff.clone()),
+    });
+    diff
+}
+
+fn append(entry: Entry) {
+    let res = path()
+        .ok_or("Couldn't retrieve config location for your system".to_owned())
+        .and_then(|path| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .map_err(|e| e.to_string())?;
+            writeln!(file, "{}", serde_json::to_string(&entry).unwrap()).map_err(|e| e.to_string())
+        });
+    if let Err(e) = res {
+        crate::error::warn(format!("couldn't record history: {}", e));
+    }
+}
+
+/// Entries about `project`(or all entries), oldest first. Renames count as
+/// entries of both the old and the new name.
+pub fn load(project: Option<&str>) -> Vec<Entry> {
+    let data = path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .unwrap_or_default();
+    data.lines()
+        .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+        .filter(|e| match project {
+            None => true,
+            Some(name) => e.project == name || (e.command == "rename" && e.new == name),
+        })
+        .collect()
+}
@@ -0,0 +1,175 @@
+//! Directories of all projects by root, cached on every full load so
+//! `touch-if-project`, which runs from shell hooks on every `cd`, can find the
+//! project of a directory without scanning the root. Entries also keep the
+//! disk usage measured by `du`, see [`crate::disk::SizeCache`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+const INDEX_FILE: &str = "index.json";
+
+type Index = HashMap<PathBuf, BTreeMap<PathBuf, Entry>>;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct Entry {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<Size>,
+}
+
+/// Disk usage of a project directory and the modification time(in seconds
+/// since the epoch) the directory had when it was measured.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Size {
+    pub bytes: u64,
+    pub mtime: i64,
+}
+
+pub(crate) fn path() -> Option<PathBuf> {
+    paths::cache(INDEX_FILE)
+}
+
+fn load() -> Index {
+    path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Replaces the cached project directories(with their names) of `root`,
+/// keeping the sizes of directories which stay. The file is only written when
+/// they changed.
+pub fn save(root: &Path, projects: BTreeMap<PathBuf, String>) -> Result<(), String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut index = load();
+    let old = index.remove(root).unwrap_or_default();
+    let unchanged = old.len() == projects.len()
+        && projects
+            .iter()
+            .all(|(dir, name)| old.get(dir).is_some_and(|e| e.name == *name));
+    let entries = projects
+        .into_iter()
+        .map(|(dir, name)| {
+            let size = old.get(&dir).and_then(|e| e.size);
+            (dir, Entry { name, size })
+        })
+        .collect();
+    index.insert(root.to_owned(), entries);
+    match unchanged {
+        true => Ok(()),
+        false => write(&path, &index),
+    }
+}
+
+/// Cached sizes of project directories of every root.
+pub fn sizes() -> HashMap<PathBuf, Size> {
+    load()
+        .into_values()
+        .flatten()
+        .filter_map(|(dir, entry)| Some((dir, entry.size?)))
+        .collect()
+}
+
+/// Sets the sizes of indexed directories, removing them for `None`.
+/// Directories which aren't indexed are skipped.
+pub fn save_sizes(sizes: &HashMap<PathBuf, Option<Size>>) -> Result<(), String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut index = load();
+    for (dir, entry) in index.values_mut().flatten() {
+        if let Some(size) = sizes.get(dir) {
+            entry.size = *size;
+        }
+    }
+    write(&path, &index)
+}
+
+fn write(path: &Path, index: &Index) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, serde_json::to_string(index).unwrap()).map_err(|e| e.to_string())
+}
+
+/// Removes project `name` of `root` from the index.
+pub fn remove(root: &Path, name: &str) -> Result<(), String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut index = load();
+    if let Some(projects) = index.get_mut(root) {
+        projects.retain(|_, e| e.name != name);
+    }
+    write(&path, &index)
+}
+
+/// Removes directories which don't exist anymore from the index of every
+/// root. Returns the removed directories.
+pub fn prune() -> Result<Vec<PathBuf>, String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+    let mut index = load();
+    let mut removed = Vec::new();
+    for projects in index.values_mut() {
+        projects.retain(|dir, _| {
+            let exists = dir.is_dir();
+            if !exists {
+                removed.push(dir.clone());
+            }
+            exists
+        });
+    }
+    index.retain(|root, projects| !projects.is_empty() || root.is_dir());
+    if !removed.is_empty() {
+        write(&path, &index)?;
+    }
+    Ok(removed)
+}
+
+/// Moves the projects of root `old` to root `new`, along with their
+/// directories inside it.
+pub fn move_root(old: &Path, new: &Path) -> Result<(), String> {
+    let path = match path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut index = load();
+    let projects = match index.remove(old) {
+        Some(projects) => projects,
+        None => return Ok(()),
+    };
+    let moved = projects
+        .into_iter()
+        .map(|(dir, entry)| match dir.strip_prefix(old) {
+            Ok(relative) => (new.join(relative), entry),
+            Err(_) => (dir, entry),
+        })
+        .collect();
+    index.insert(new.to_owned(), moved);
+    write(&path, &index)
+}
+
+/// Name and directory of the innermost project of `root` containing `dir`.
+pub fn lookup(root: &Path, dir: &Path) -> Option<(String, PathBuf)> {
+    load()
+        .remove(root)?
+        .into_iter()
+        .filter(|(path, _)| dir.starts_with(path))
+        .max_by_key(|(path, _)| path.components().count())
+        .map(|(path, entry)| (entry.name, path))
+}
@@ -0,0 +1,38 @@
+//! Manage folders of projects. The `cli-project-manager` binary is a thin
+//! wrapper around `app`, the other modules can be used to build different
+//! front-ends over the same projects.
+
+pub mod app;
+pub mod archive;
+pub mod backup;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod crypt;
+pub mod dashboard;
+pub mod disk;
+pub mod error;
+pub mod executor;
+pub mod export;
+pub mod format;
+pub mod git;
+pub mod history;
+pub mod index;
+pub mod names;
+pub mod paths;
+pub mod plugin;
+pub mod progress;
+pub mod project;
+pub mod registry;
+pub mod scripting;
+pub mod serve;
+pub mod stats;
+pub mod storage;
+pub mod tasks;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timing;
+pub mod trash;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod workspace;
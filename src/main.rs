@@ -1,13 +1,22 @@
-mod app;
-mod cli;
-mod config;
-mod project;
+use cli_project_manager::{app, cli, config, timing::Timings};
 
 // TODO : gen completion
 
 fn main() {
-    // TODO : make config customizable
-    let conf = config::Config::new();
+    app::guard_terminal();
     let matches = cli::build().get_matches();
-    app::handle(conf, matches);
+    // config subcommands must work even when config file is missing or broken
+    if let Some(("config", args)) = matches.subcommand() {
+        return app::handle_config(args);
+    }
+    // TODO : make config customizable
+    let mut timings = Timings::default();
+    let conf = timings.time("config", || {
+        config::Config::new(
+            matches
+                .get_one::<String>("profile")
+                .map(|profile| profile.as_str()),
+        )
+    });
+    app::handle(conf, matches, timings);
 }
@@ -0,0 +1,44 @@
+//! Comparing project names the way case-insensitive and normalizing
+//! filesystems(the defaults on macOS and Windows) do, where `Proj0` and
+//! `proj0`, or `é` typed precomposed and as `e` plus an accent, are the same
+//! directory.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Key which is equal for names such filesystems treat as the same: NFC
+/// composed and case folded, so `ß` and `ss` or `Σ` and `ς` compare equal
+/// too.
+pub fn fold(name: &str) -> String {
+    caseless::default_case_fold_str(&name.nfc().collect::<String>())
+        .nfc()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decomposed_name_folds_like_composed() {
+        assert_eq!(fold("Cafe\u{301}"), fold("café"));
+        assert_eq!(fold("PROJ-\u{c9}"), fold("proj-e\u{301}"));
+    }
+
+    #[test]
+    fn non_latin_names_fold() {
+        // Cyrillic й is и followed by a breve
+        assert_eq!(fold("\u{438}\u{306}"), fold("\u{419}"));
+        // Greek ά is α followed by a tonos
+        assert_eq!(fold("\u{3b1}\u{301}"), fold("\u{386}"));
+        // Hangul syllable 한 from its jamo
+        assert_eq!(fold("\u{1112}\u{1161}\u{11ab}"), "\u{d55c}");
+        assert_eq!(fold("STRASSE"), fold("straße"));
+    }
+
+    #[test]
+    fn marks_are_reordered_before_composing() {
+        // dot below and circumflex in either order are the same ệ
+        assert_eq!(fold("e\u{302}\u{323}"), fold("e\u{323}\u{302}"));
+        assert_eq!(fold("e\u{302}\u{323}"), "\u{1ec7}");
+    }
+}
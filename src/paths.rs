@@ -0,0 +1,51 @@
+//! Where the program keeps its own files, following the XDG base directories
+//! on Linux and the usual places on other systems(through `dirs`):
+//!
+//! - config: the config file
+//! - state: history and backups, worth keeping but not meant to be edited
+//! - data: the registry of external projects
+//! - cache: the project index, directory sizes and workspaces, which can be
+//!   deleted anytime
+//!
+//! Deleted projects stay in the trash directory of their root so deleting
+//! and restoring them are renames on the same filesystem.
+
+use std::{fs, path::PathBuf};
+
+const APP_DIR: &str = "cli-project-manager";
+const CONFIG_FILE: &str = "cli-project-manager.json";
+
+pub fn config_file() -> PathBuf {
+    dirs::config_dir()
+        .expect("Couldn't retrieve config location for your system")
+        .join(CONFIG_FILE)
+}
+
+/// File or directory `name` in the state directory(the local data directory
+/// on systems without one). Older versions kept state in the config
+/// directory, what's left there is moved over the first time.
+pub fn state(name: &str) -> Option<PathBuf> {
+    let path = dirs::state_dir()
+        .or_else(dirs::data_local_dir)?
+        .join(APP_DIR)
+        .join(name);
+    let legacy = dirs::config_dir().map(|d| d.join(APP_DIR).join(name));
+    if let Some(legacy) = legacy.filter(|l| l.exists() && !path.exists() && *l != path) {
+        // if it fails the old one is only ignored, nothing is lost
+        let _ = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::rename(&legacy, &path));
+    }
+    Some(path)
+}
+
+/// File or directory `name` in the data directory.
+pub fn data(name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join(APP_DIR).join(name))
+}
+
+/// File or directory `name` in the cache directory.
+pub fn cache(name: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join(APP_DIR).join(name))
+}
@@ -0,0 +1,29 @@
+use std::{ffi::OsString, io::ErrorKind, process::Command};
+
+use crate::config::Config;
+
+pub const PREFIX: &str = "pj-";
+
+/// Runs `pj-<name>` from PATH with the remaining arguments, the way git and
+/// cargo dispatch unknown subcommands. The plugin gets the config path and
+/// project root through `PM_CONFIG` and `PM_ROOT`(and the selected profile
+/// through `PM_PROFILE`).
+/// Returns the exit code of the plugin.
+pub fn run(conf: &Config, name: &str, args: Vec<OsString>) -> Result<i32, String> {
+    let program = format!("{}{}", PREFIX, name);
+    let mut cmd = Command::new(&program);
+    cmd.args(args)
+        .env("PM_CONFIG", Config::path())
+        .env("PM_ROOT", &conf.dir);
+    if let Some(profile) = &conf.profile {
+        cmd.env("PM_PROFILE", profile);
+    }
+    let status = cmd.status().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => format!(
+            "no such subcommand '{}' and no '{}' found in PATH",
+            name, program
+        ),
+        _ => format!("Couldn't run {}: {}", program, e),
+    })?;
+    Ok(status.code().unwrap_or(-1))
+}
@@ -0,0 +1,42 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::format::interactive;
+
+/// Progress bar for operations over many projects, drawn on stderr. It's
+/// hidden when `quiet` is set or output isn't [`interactive`] so piped output
+/// and scripts aren't affected. The bar is cleared when dropped.
+pub struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    pub fn new(quiet: bool) -> Self {
+        let bar = if quiet || !interactive() {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(0).with_style(
+                ProgressStyle::with_template("{bar:30} {pos}/{len} {wide_msg}")
+                    .unwrap()
+                    .progress_chars("=> "),
+            )
+        };
+        Progress { bar }
+    }
+    /// Shows that `done` out of `total` items are finished and `current` is
+    /// being worked on.
+    pub fn update(&self, done: usize, total: usize, current: &str) {
+        self.bar.set_length(total as u64);
+        self.bar.set_position(done as u64);
+        self.bar.set_message(current.to_owned());
+    }
+    /// Prints `line` to stdout without breaking the bar.
+    pub fn println(&self, line: impl AsRef<str>) {
+        self.bar.suspend(|| println!("{}", line.as_ref()));
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -1,19 +1,40 @@
 // TODO : make sure search works with substrings
-use core::panic;
+use crate::{
+    archive::{ARCHIVE_DIR, ARCHIVE_FILE},
+    error::{self, LoadProblem, ProjectError, ProjectErrorTypes, Severity},
+    git, names,
+    registry::Registry,
+    storage::{Disk, Memory, Storage},
+    trash::{self, TRASH_DIR},
+};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
-    cmp::Reverse, collections::HashSet, fmt::Display, fs, io::Write, path::PathBuf,
+    cmp::Ordering,
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
     process::Command,
+    str::FromStr,
+    time::Instant,
 };
+
 use time::{
     format_description::well_known::{
         iso8601::{self, TimePrecision},
         Iso8601,
     },
-    OffsetDateTime,
+    Date, Duration, OffsetDateTime,
 };
 
-const PROJECT_FILE: &str = ".project.json";
+pub(crate) const PROJECT_FILE: &str = ".project.json";
+/// File in the root listing directories which aren't projects, like a
+/// `.gitignore`.
+pub const IGNORE_FILE: &str = ".pmignore";
+/// Most metadata files written at once by [`ProjectManager::save_all`].
+pub const SAVE_THREADS: usize = 8;
 const TIME_CONFIG: iso8601::EncodedConfig = iso8601::Config::DEFAULT
     .set_year_is_six_digits(false)
     .set_time_precision(TimePrecision::Second {
@@ -22,62 +43,504 @@ const TIME_CONFIG: iso8601::EncodedConfig = iso8601::Config::DEFAULT
     .encode();
 const TIME_FORMAT: Iso8601<TIME_CONFIG> = Iso8601::<TIME_CONFIG>;
 time::serde::format_description!(time_format, OffsetDateTime, TIME_FORMAT);
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
 
+#[derive(Clone, Copy)]
 pub enum SortOrder {
     Creation,
     AccessTime,
     Name,
+    Priority,
+    Modified,
+    Completed,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "created" => Ok(SortOrder::Creation),
+            "accessed" => Ok(SortOrder::AccessTime),
+            "name" => Ok(SortOrder::Name),
+            "priority" => Ok(SortOrder::Priority),
+            "modified" => Ok(SortOrder::Modified),
+            "completed" => Ok(SortOrder::Completed),
+            other => Err(format!(
+                "Unknown sort key '{}'(expected created, accessed, modified, completed, name or priority)",
+                other
+            )),
+        }
+    }
+}
+
+impl SortOrder {
+    /// Newest first for times, alphabetical for names and highest first for
+    /// priorities(projects without one, or not completed, last).
+    fn compare(&self, a: &Project, b: &Project) -> Ordering {
+        match self {
+            SortOrder::Creation => b.created.cmp(&a.created),
+            SortOrder::AccessTime => b.accessed.cmp(&a.accessed),
+            SortOrder::Name => a.name.cmp(&b.name),
+            SortOrder::Priority => b.priority.cmp(&a.priority),
+            SortOrder::Modified => b.get_modified().cmp(&a.get_modified()),
+            SortOrder::Completed => b.completed.cmp(&a.completed),
+        }
+    }
+}
+
+/// How an archived project is kept in the archive directory of root.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Archived {
+    /// The directory was moved as is
+    Moved,
+    /// Only a compressed tarball of the directory is kept
+    Compressed,
+}
+
+impl Display for Archived {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Archived::Moved => write!(f, "archived"),
+            Archived::Compressed => write!(f, "archived(compressed)"),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Project {
+    /// UUID which stays the same when the project is renamed. Projects
+    /// created before ids existed get one the next time they're saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     name: String,
     #[serde(with = "time_format")]
     created: OffsetDateTime,
     #[serde(with = "time_format")]
     accessed: OffsetDateTime,
     tags: HashSet<String>,
+    /// Last time the metadata changed, other than accessed times. Projects
+    /// from before it was tracked count as modified when created
+    #[serde(
+        default,
+        with = "time_format::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    modified: Option<OffsetDateTime>,
+    /// When work on the project ended, see `modify --completed`
+    #[serde(
+        default,
+        with = "date_format::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    completed: Option<Date>,
+    /// Readable name shown instead of `name` when it had to be slugified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(
+        default,
+        with = "date_format::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    due: Option<Date>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archived: Option<Archived>,
+    /// Higher is more important
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    /// Git worktrees of the project by branch, see `worktree add`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    worktrees: BTreeMap<String, PathBuf>,
+    /// Directories(relative to the project) loaded as separate projects, like
+    /// the packages of a monorepo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    subprojects: Vec<PathBuf>,
+    /// User responsible for the project, the creator by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    /// Refuses renaming, modifying and deleting the project unless unlocked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    locked: bool,
+    /// Left out of `find` and `list` unless they're given `--all`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    hidden: bool,
+    /// Title and custom values encrypted by [`ProjectManager::seal`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<String>,
+    /// Command last run in the project with `exec -c`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_command: Option<String>,
+    /// Any other values users want to keep about the project, like "owner"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    custom: BTreeMap<String, serde_json::Value>,
 }
 
 impl Project {
     pub fn new(name: String, created_time: OffsetDateTime, tags: HashSet<String>) -> Self {
         Project {
+            id: Some(new_id()),
             name,
             created: created_time,
             accessed: created_time,
             tags,
+            modified: None,
+            completed: None,
+            title: None,
+            due: None,
+            archived: None,
+            priority: None,
+            worktrees: BTreeMap::new(),
+            subprojects: Vec::new(),
+            owner: None,
+            locked: false,
+            last_command: None,
+            hidden: false,
+            sealed: None,
+            custom: BTreeMap::new(),
         }
     }
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+    pub fn with_owner(mut self, owner: String) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+    pub fn get_owner(&self) -> Option<&String> {
+        self.owner.as_ref()
+    }
+    pub fn get_last_command(&self) -> Option<&String> {
+        self.last_command.as_ref()
+    }
+    pub fn get_title(&self) -> Option<&String> {
+        self.title.as_ref()
+    }
+    pub fn get_due(&self) -> Option<Date> {
+        self.due
+    }
+    pub fn get_priority(&self) -> Option<u8> {
+        self.priority
+    }
+    pub fn get_archived(&self) -> Option<Archived> {
+        self.archived
+    }
+    pub fn get_worktrees(&self) -> &BTreeMap<String, PathBuf> {
+        &self.worktrees
+    }
+    pub fn get_subprojects(&self) -> &[PathBuf] {
+        &self.subprojects
+    }
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+    pub fn get_custom(&self, key: &str) -> Option<&serde_json::Value> {
+        self.custom.get(key)
+    }
+    pub fn get_all_custom(&self) -> &BTreeMap<String, serde_json::Value> {
+        &self.custom
+    }
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.is_some()
+    }
+    /// Copy of the project with the title and custom values `decrypt` gets
+    /// back from the sealed ones.
+    pub fn unsealed(
+        &self,
+        decrypt: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<Project, String> {
+        let sealed = self
+            .sealed
+            .as_ref()
+            .ok_or_else(|| format!("'{}' isn't encrypted", self.name))?;
+        let secrets: Secrets = serde_json::from_str(&decrypt(sealed)?)
+            .map_err(|e| format!("Couldn't decrypt '{}': {}", self.name, e))?;
+        let mut project = self.clone();
+        project.sealed = None;
+        project.title = secrets.title;
+        project.custom = secrets.custom;
+        Ok(project)
+    }
     pub fn get_tags(&self) -> HashSet<String> {
         self.tags.clone()
     }
+    /// Whether `pattern` matches the name, title or one of the tags.
+    pub fn matches(&self, pattern: &Regex) -> bool {
+        pattern.is_match(&self.name)
+            || self.title.as_ref().is_some_and(|t| pattern.is_match(t))
+            || self.tags.iter().any(|t| pattern.is_match(t))
+    }
     pub fn get_name(&self) -> &String {
         &self.name
     }
+    pub fn get_id(&self) -> Option<&String> {
+        self.id.as_ref()
+    }
+    pub fn get_created(&self) -> OffsetDateTime {
+        self.created
+    }
+    pub fn get_accessed(&self) -> OffsetDateTime {
+        self.accessed
+    }
+    pub fn get_modified(&self) -> OffsetDateTime {
+        self.modified.unwrap_or(self.created)
+    }
+    pub fn get_completed(&self) -> Option<Date> {
+        self.completed
+    }
     fn rename(&mut self, name: String) {
         self.name = name
     }
     fn modify(&mut self, new_tags: HashSet<String>) {
         self.tags = new_tags
     }
-    fn save(&self, path: PathBuf) -> Result<(), String> {
-        let res = fs::write(
-            path.join(PROJECT_FILE),
-            serde_json::to_string(self).unwrap(),
-        );
-        if let Err(e) = res {
-            return Err(e.to_string());
+    fn save(&self, storage: &dyn Storage, path: PathBuf) -> Result<(), String> {
+        storage.write(
+            &path.join(PROJECT_FILE),
+            &serde_json::to_string(self).unwrap(),
+        )
+    }
+}
+
+/// Command running `cmd` in project directory `path`. `{}` and `{path}` in
+/// `cmd` are replaced with the path.
+fn command(path: &Path, cmd: &str) -> Command {
+    let cmd = cmd
+        .replace("{path}", &path.to_string_lossy())
+        .replace("{}", &path.to_string_lossy());
+    let cmd: Vec<&str> = cmd.split(' ').collect();
+    let mut command = Command::new(cmd[0]);
+    command.args(&cmd[1..]).current_dir(path);
+    command
+}
+
+/// Directory `relative` inside project directory `path`, which has to exist
+/// and stay inside the project once `..` and symlinks are resolved.
+pub fn subdirectory(path: &Path, relative: &Path) -> Result<PathBuf, String> {
+    let dir = path.join(relative);
+    let resolved = dir
+        .canonicalize()
+        .map_err(|e| format!("Couldn't use {:?}: {}", dir, e))?;
+    if !resolved.starts_with(path.canonicalize().unwrap_or(path.to_owned())) {
+        return Err(format!("{:?} isn't inside {:?}", relative, path));
+    }
+    if !resolved.is_dir() {
+        return Err(format!("{:?} isn't a directory", dir));
+    }
+    Ok(resolved)
+}
+
+/// What running `cmd` in project directory `path` would do: the executable
+/// (found through `PATH`), its arguments, directory and added environment.
+pub fn explain(path: &Path, cmd: &str) -> String {
+    let command = command(path, cmd);
+    let program = Path::new(command.get_program());
+    let resolved = if program.components().count() > 1 {
+        Some(program.to_owned())
+    } else {
+        std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(program))
+                .find(|p| p.is_file())
+        })
+    };
+    let args: Vec<_> = command.get_args().collect();
+    let envs: Vec<String> = command
+        .get_envs()
+        .map(|(k, v)| format!("{}={:?}", k.to_string_lossy(), v.unwrap_or_default()))
+        .collect();
+    format!(
+        "executable   {}\narguments    {:?}\ndirectory    {}\nenvironment  {}\n",
+        match resolved {
+            Some(p) => p.display().to_string(),
+            None => format!("{} (not found in PATH)", program.display()),
+        },
+        args,
+        path.display(),
+        match envs.is_empty() {
+            true => String::from("inherited, nothing added"),
+            false => envs.join(" "),
         }
-        Ok(())
+    )
+}
+
+/// Random(version 4) UUID for a new project.
+fn new_id() -> String {
+    // randomly seeded hashers are random enough for ids too
+    let random = || RandomState::new().build_hasher().finish();
+    let (a, b) = (random(), random());
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        (b >> 48) & 0x3fff | 0x8000,
+        b & 0xffff_ffff_ffff
+    )
+}
+
+/// Values of a project which are encrypted when it's sealed.
+#[derive(Serialize, Deserialize)]
+struct Secrets {
+    title: Option<String>,
+    custom: BTreeMap<String, serde_json::Value>,
+}
+
+/// Parses metadata the way it's stored, rejecting fields this version
+/// doesn't know about instead of dropping them.
+pub fn parse_metadata(data: &str) -> Result<Project, String> {
+    let mut unknown = Vec::new();
+    let project =
+        serde_ignored::deserialize(&mut serde_json::Deserializer::from_str(data), |field| {
+            unknown.push(field.to_string())
+        })
+        .map_err(|e| e.to_string())?;
+    match unknown.first() {
+        Some(field) => Err(format!("Unknown field '{}'", field)),
+        None => Ok(project),
+    }
+}
+
+/// Name of the user running the program, used as owner of new projects.
+pub fn current_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
+/// Shell-friendly form of `name`: lowercase alphanumeric words joined by `-`,
+/// like "my-cool-app" for "My Cool App".
+pub fn slugify(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A sed-like substitution on project names, `s/pattern/replacement/` with
+/// a trailing `g` replacing every match instead of the first. `\1` and `$1`
+/// both refer to capture groups. Any character can be the delimiter, it's
+/// escaped with a backslash inside the pattern and replacement.
+#[derive(Clone)]
+pub struct Substitution {
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl FromStr for Substitution {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid substitution '{}'(expected s/pattern/replacement/)",
+                s
+            )
+        };
+        let rest = s.strip_prefix('s').ok_or_else(invalid)?;
+        let delimiter = rest.chars().next().ok_or_else(invalid)?;
+        let mut parts = vec![String::new()];
+        let mut chars = rest[delimiter.len_utf8()..].chars();
+        while let Some(c) = chars.next() {
+            let part = parts.last_mut().unwrap();
+            match c {
+                '\\' => match chars.next() {
+                    Some(next) if next == delimiter => part.push(next),
+                    Some(next) => part.extend(['\\', next]),
+                    None => part.push(c),
+                },
+                _ if c == delimiter => parts.push(String::new()),
+                _ => part.push(c),
+            }
+        }
+        let (pattern, replacement, flags) = match &parts[..] {
+            [pattern, replacement, flags] => (pattern, replacement, flags),
+            _ => return Err(invalid()),
+        };
+        if !flags.chars().all(|c| c == 'g') {
+            return Err(format!(
+                "Unknown flags '{}' in substitution(only g is supported)",
+                flags
+            ));
+        }
+        let backreference = Regex::new(r"\\(\d)").unwrap();
+        Ok(Substitution {
+            pattern: Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?,
+            replacement: backreference
+                .replace_all(replacement, "$${$1}")
+                .into_owned(),
+            global: !flags.is_empty(),
+        })
+    }
+}
+
+impl Substitution {
+    /// New name for `name`, None if the pattern doesn't match it.
+    pub fn apply(&self, name: &str) -> Option<String> {
+        if !self.pattern.is_match(name) {
+            return None;
+        }
+        let res = match self.global {
+            true => self.pattern.replace_all(name, self.replacement.as_str()),
+            false => self.pattern.replace(name, self.replacement.as_str()),
+        };
+        Some(res.into_owned())
     }
 }
 
+/// Finds the managed project containing `dir` without scanning the whole root.
+/// Projects are direct children of `root`, so only one candidate is checked.
+/// Returns the project directory and its metadata. Paths which don't exist on
+/// disk, like those of [`Memory`], are used as given.
+pub fn find_containing(
+    storage: &dyn Storage,
+    root: &Path,
+    dir: &Path,
+) -> Option<(PathBuf, Project)> {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_owned());
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+    let name = dir.strip_prefix(&root).ok()?.components().next()?;
+    let path = root.join(name);
+    let data = storage.read(&path.join(PROJECT_FILE)).ok()?;
+    let project = serde_json::from_str::<Project>(&data).ok()?;
+    Some((path, project))
+}
+
+/// Forgets project `name` which lived in `dir` under `root` after its
+/// directory disappeared: it's removed from the external projects and the
+/// index so it stops showing up.
+pub fn forget(root: &Path, name: &str, dir: &Path) -> Result<(), String> {
+    let mut registry = Registry::load();
+    if registry.get(root).iter().any(|d| d == dir) {
+        registry.remove(root, dir)?;
+    }
+    crate::index::remove(root, name)
+}
+
+/// Marks the project in directory `dir` as accessed now without loading any
+/// other project, for callers which run too often to scan the root.
+pub fn touch_dir(storage: &dyn Storage, dir: &Path) -> Result<(), String> {
+    let data = storage.read(&dir.join(PROJECT_FILE))?;
+    let mut project = serde_json::from_str::<Project>(&data).map_err(|e| e.to_string())?;
+    project.accessed = OffsetDateTime::now_utc();
+    project.save(storage, dir.to_owned())
+}
+
 impl Display for Project {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(title) = &self.title {
+            write!(f, "{} ({})", title, self.name)?;
+        } else {
+            write!(f, "{}", self.name)?;
+        }
         write!(
             f,
-            "{}: {}",
-            self.name,
+            ": {}",
             self.tags
                 .clone()
                 .into_iter()
@@ -90,123 +553,1528 @@ impl Display for Project {
 pub struct ProjectManager {
     root: PathBuf,
     projects: Vec<Project>,
+    archived: Vec<Project>,
     tags: HashSet<String>,
+    external: HashMap<String, PathBuf>, // projects outside root by name
+    subprojects: HashMap<String, String>, // parents of subprojects by name
+    errors: Vec<LoadProblem>,           // problems found by the last scan
+    stats: ScanStats,                   // time spent by the last scan
+    normalize: bool,                    // whether tags are lowercased
+    unlocked: bool,                     // whether locked projects can be changed
+    deferred: Option<HashSet<String>>,  // unsaved accessed times while batching
+    scaffold: Vec<(PathBuf, String)>,   // files written into new projects
+    registry: Registry,
+    storage: Box<dyn Storage>,
 }
 
-impl ProjectManager {
-    pub fn load(path: PathBuf) -> Self {
-        let mut projects = Vec::<Project>::new();
-        let mut tags = HashSet::<String>::new();
-        if !path.is_dir() {
-            panic!("Root directory({path:?}) not found or not a directory!");
+fn project_path(root: &Path, external: &HashMap<String, PathBuf>, name: &str) -> PathBuf {
+    match external.get(name) {
+        Some(path) => path.clone(),
+        None => root.join(name),
+    }
+}
+
+/// Creates the project directory if needed and writes its metadata.
+fn init_dir(storage: &dyn Storage, path: &Path, project: &Project) -> Result<(), String> {
+    if !storage.is_dir(path) {
+        storage.create_dir(path)?;
+    }
+    storage.append(&path.join(".gitignore"), &format!("{}\n", PROJECT_FILE))?;
+    project.save(storage, path.to_owned())
+}
+
+/// Result of reading the metadata of every project under a root.
+struct Scan {
+    projects: Vec<Project>,
+    archived: Vec<Project>,
+    external: HashMap<String, PathBuf>,
+    subprojects: HashMap<String, String>,
+    errors: Vec<LoadProblem>,
+    stats: ScanStats,
+}
+
+/// Problems found by a scan, each passed to `listener` right away.
+struct Problems<'a> {
+    found: Vec<LoadProblem>,
+    listener: &'a mut dyn FnMut(&LoadProblem),
+}
+
+impl Problems<'_> {
+    fn push(&mut self, severity: Severity, error: ProjectError) {
+        #[cfg(feature = "tracing")]
+        match severity {
+            Severity::Warning => tracing::warn!(kind = ?error.kind, "{}", error),
+            Severity::Error => tracing::error!(kind = ?error.kind, "{}", error),
         }
+        let problem = LoadProblem { severity, error };
+        (self.listener)(&problem);
+        self.found.push(problem);
+    }
+}
 
-        for entry in fs::read_dir(&path).unwrap() {
-            let entry = entry.unwrap().path();
-            if entry.is_dir()
-                && entry
-                    .read_dir()
-                    .unwrap()
-                    .any(|f| f.unwrap().file_name() == PROJECT_FILE)
-            {
-                let data = fs::read_to_string(entry.join(PROJECT_FILE)).unwrap_or_else(|e| {
-                    panic!("Couldn't read {} in {:?}: {}", PROJECT_FILE, entry, e)
-                });
-                let project = serde_json::from_str::<Project>(&data);
-                if let Ok(p) = project {
-                    tags.extend(p.tags.clone());
+/// Where the time of a scan went, see [`ProjectManager::scan_stats`].
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ScanStats {
+    /// Directories looked at, including the archive, external projects and
+    /// subprojects
+    pub dirs: usize,
+    /// Time spent reading metadata files
+    pub read: std::time::Duration,
+    /// Time spent parsing metadata
+    pub parse: std::time::Duration,
+}
+
+impl ScanStats {
+    /// Reads and parses the metadata at `path`, timing both.
+    fn read(&mut self, storage: &dyn Storage, path: &Path) -> Result<Project, String> {
+        let start = Instant::now();
+        let data = storage.read(path);
+        self.read += start.elapsed();
+        let start = Instant::now();
+        let project =
+            data.and_then(|data| serde_json::from_str::<Project>(&data).map_err(|e| e.to_string()));
+        self.parse += start.elapsed();
+        project
+    }
+}
+
+/// Innermost project directory containing `root`, or `root` itself when it
+/// has metadata.
+fn enclosing_project(storage: &dyn Storage, root: &Path) -> Option<PathBuf> {
+    root.ancestors()
+        .find(|dir| storage.is_file(&dir.join(PROJECT_FILE)))
+        .map(Path::to_owned)
+}
+
+fn nested_root(root: &Path, project: &Path) -> ProjectError {
+    let message = match root == project {
+        true => format!("root {:?} is a project itself", root),
+        false => format!("root {:?} is inside project {:?}", root, project),
+    };
+    ProjectError::new(
+        ProjectErrorTypes::NestedRoot,
+        format!(
+            "{}, set dir in the config to the directory containing your projects",
+            message
+        ),
+    )
+}
+
+/// Matcher of the `.pmignore` in `root`, in gitignore syntax. A missing file
+/// ignores nothing and invalid lines are reported as warnings.
+fn ignore_file(storage: &dyn Storage, root: &Path, problems: &mut Problems) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    let path = root.join(IGNORE_FILE);
+    if storage.is_file(&path) {
+        for line in storage.read(&path).unwrap_or_default().lines() {
+            // globs are only checked when building, so each line is tried on
+            // its own first to keep one bad line from disabling the others
+            let mut line_builder = GitignoreBuilder::new(root);
+            let valid = line_builder
+                .add_line(Some(path.clone()), line)
+                .and_then(|b| b.build().map(|_| ()));
+            match valid {
+                Ok(()) => {
+                    builder.add_line(Some(path.clone()), line).unwrap();
+                }
+                Err(e) => problems.push(
+                    Severity::Warning,
+                    ProjectError::new(
+                        ProjectErrorTypes::Other,
+                        format!("invalid line in {:?}: {}", path, e),
+                    ),
+                ),
+            }
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// Reads the metadata of every project under `root` and of the external
+/// projects registered for it. Directories matching the `.pmignore` of the
+/// root are skipped. Broken or conflicting projects are skipped and
+/// reported as errors, to `listener` as well. `progress` is called with the
+/// number of directories read so far, their total and the one being read.
+fn scan(
+    storage: &dyn Storage,
+    root: &Path,
+    registry: &Registry,
+    progress: &mut dyn FnMut(usize, usize, &Path),
+    listener: &mut dyn FnMut(&LoadProblem),
+) -> Scan {
+    let mut projects = Vec::<Project>::new();
+    let mut errors = Problems {
+        found: Vec::new(),
+        listener,
+    };
+    let mut stats = ScanStats::default();
+    let broken = |dir: &Path, e: String| {
+        ProjectError::new(
+            ProjectErrorTypes::BrokenMetadata,
+            format!("broken {} at {:?}: {}", PROJECT_FILE, dir, e),
+        )
+    };
+    if let Some(project) = enclosing_project(storage, root) {
+        errors.push(Severity::Warning, nested_root(root, &project));
+    }
+    let ignore = ignore_file(storage, root, &mut errors);
+    let entries: Vec<PathBuf> = storage
+        .list_dirs(root)
+        .unwrap()
+        .into_iter()
+        .filter(|entry| !ignore.matched(entry, true).is_ignore())
+        .collect();
+    let (scanned, total) = (entries.len(), entries.len() + registry.get(root).len());
+    stats.dirs += total;
+    for (i, entry) in entries.into_iter().enumerate() {
+        progress(i, total, &entry);
+        if storage.is_file(&entry.join(PROJECT_FILE)) {
+            let project = stats.read(storage, &entry.join(PROJECT_FILE));
+            match project {
+                Ok(p) => projects.push(p),
+                Err(e) => errors.push(Severity::Error, broken(&entry, e)),
+            }
+        }
+    }
+
+    let mut archived = Vec::new();
+    let archive = root.join(ARCHIVE_DIR);
+    for entry in storage.list_dirs(&archive).unwrap_or_default() {
+        stats.dirs += 1;
+        let project = stats.read(storage, &entry.join(PROJECT_FILE));
+        match project {
+            Ok(p) => archived.push(p),
+            Err(e) => errors.push(Severity::Error, broken(&entry, e)),
+        }
+    }
+
+    let mut external = HashMap::new();
+    for (i, dir) in registry.get(root).iter().enumerate() {
+        progress(scanned + i, total, dir);
+        let project = stats.read(storage, &dir.join(PROJECT_FILE));
+        match project {
+            Ok(p) if projects.iter().any(|other| other.name == p.name) => errors.push(
+                Severity::Error,
+                ProjectError::new(
+                    ProjectErrorTypes::DuplicateName,
+                    format!(
+                        "external project at {:?} has the same name as another project",
+                        dir
+                    ),
+                ),
+            ),
+            Ok(p) => {
+                external.insert(p.name.clone(), dir.clone());
+                projects.push(p);
+            }
+            Err(e) => errors.push(Severity::Error, broken(dir, e)),
+        }
+    }
+
+    // only one level deep, subprojects of subprojects aren't loaded
+    let mut subprojects = HashMap::new();
+    let parents: Vec<(String, PathBuf, Vec<PathBuf>, OffsetDateTime)> = projects
+        .iter()
+        .filter(|p| !p.subprojects.is_empty())
+        .map(|p| {
+            let path = project_path(root, &external, &p.name);
+            (p.name.clone(), path, p.subprojects.clone(), p.created)
+        })
+        .collect();
+    for (parent, path, dirs, created) in parents {
+        for relative in dirs {
+            let dir = path.join(&relative);
+            stats.dirs += 1;
+            // listed directories without metadata get it once they're changed
+            let project = match storage.is_file(&dir.join(PROJECT_FILE)) {
+                true => stats.read(storage, &dir.join(PROJECT_FILE)),
+                false if storage.is_dir(&dir) => {
+                    let name = format!("{}-{}", parent, relative.to_string_lossy());
+                    Ok(Project::new(
+                        name.replace('/', "-"),
+                        created,
+                        HashSet::new(),
+                    ))
+                }
+                false => Err(format!("subproject of '{}' doesn't exist", parent)),
+            };
+            match project {
+                Ok(p) if projects.iter().any(|other| other.name == p.name) => errors.push(
+                    Severity::Error,
+                    ProjectError::new(
+                        ProjectErrorTypes::DuplicateName,
+                        format!(
+                            "subproject at {:?} has the same name as another project",
+                            dir
+                        ),
+                    ),
+                ),
+                Ok(p) => {
+                    external.insert(p.name.clone(), dir);
+                    subprojects.insert(p.name.clone(), parent.clone());
                     projects.push(p);
-                } else {
-                    println!("WARNING: broken {} at {:?}", PROJECT_FILE, entry);
                 }
+                Err(e) => errors.push(Severity::Error, broken(&dir, e)),
             }
         }
-        Self {
+    }
+    let mut seen: HashMap<String, &String> = HashMap::new();
+    for project in &projects {
+        match seen.get(&names::fold(&project.name)) {
+            Some(other) => errors.push(
+                Severity::Warning,
+                ProjectError::name_collision(&project.name, other),
+            ),
+            None => {
+                seen.insert(names::fold(&project.name), &project.name);
+            }
+        }
+    }
+    Scan {
+        projects,
+        archived,
+        external,
+        subprojects,
+        errors: errors.found,
+        stats,
+    }
+}
+
+impl Drop for ProjectManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error::warn(format!("couldn't save accessed times: {}", e));
+        }
+    }
+}
+
+/// Names of the projects touched by [`ProjectManager::reload`].
+#[derive(Default, Debug)]
+pub struct Reload {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ProjectManager {
+    pub fn load(path: PathBuf) -> Result<Self, ProjectError> {
+        Self::load_with(path, Box::new(Disk))
+    }
+    /// Loads projects of root directory `path` from `storage` instead of disk.
+    pub fn load_with(path: PathBuf, storage: Box<dyn Storage>) -> Result<Self, ProjectError> {
+        Self::load_with_progress(path, storage, &mut |_, _, _| {})
+    }
+    /// Like `load_with` but reports each directory while scanning the root,
+    /// see [`crate::progress::Progress`].
+    pub fn load_with_progress(
+        path: PathBuf,
+        storage: Box<dyn Storage>,
+        progress: &mut dyn FnMut(usize, usize, &Path),
+    ) -> Result<Self, ProjectError> {
+        Self::load_with_events(path, storage, progress, &mut |_| {})
+    }
+    /// Like `load_with_progress` but also passes each problem to `listener`
+    /// as soon as it's found, so applications embedding the manager can show
+    /// them while a large root is still being scanned. They're also emitted
+    /// as `tracing` events with the tracing feature.
+    pub fn load_with_events(
+        path: PathBuf,
+        storage: Box<dyn Storage>,
+        progress: &mut dyn FnMut(usize, usize, &Path),
+        listener: &mut dyn FnMut(&LoadProblem),
+    ) -> Result<Self, ProjectError> {
+        if !storage.is_dir(&path) {
+            return Err(ProjectError::new(
+                ProjectErrorTypes::Other,
+                format!("Root directory({:?}) not found or not a directory", path),
+            ));
+        }
+        let registry = storage.registry();
+        let scan = scan(storage.as_ref(), &path, &registry, progress, listener);
+        Ok(Self {
             root: path,
+            tags: scan.projects.iter().flat_map(|p| p.tags.clone()).collect(),
+            projects: scan.projects,
+            archived: scan.archived,
+            external: scan.external,
+            subprojects: scan.subprojects,
+            errors: scan.errors,
+            stats: scan.stats,
+            normalize: false,
+            unlocked: false,
+            deferred: None,
+            scaffold: Vec::new(),
+            registry,
+            storage,
+        })
+    }
+    /// Re-scans the root and applies only what differs from the projects in
+    /// memory: new directories are added, missing ones dropped and projects
+    /// whose metadata changed on disk are replaced. Renames show up as a
+    /// removal and an addition.
+    pub fn reload(&mut self) -> Reload {
+        let scan = scan(
+            self.storage.as_ref(),
+            &self.root,
+            &self.registry,
+            &mut |_, _, _| {},
+            &mut |_| {},
+        );
+        let mut projects = scan.projects;
+        for project in &mut projects {
+            project.tags = self.normalize_set(std::mem::take(&mut project.tags));
+        }
+        let mut res = Reload::default();
+        self.projects.retain(|p| {
+            let keep = projects.iter().any(|other| other.name == p.name);
+            if !keep {
+                res.removed.push(p.name.clone());
+            }
+            keep
+        });
+        for project in projects {
+            match self.projects.iter_mut().find(|p| p.name == project.name) {
+                Some(old) if *old != project => {
+                    res.changed.push(project.name.clone());
+                    *old = project;
+                }
+                Some(_) => {}
+                None => {
+                    res.added.push(project.name.clone());
+                    self.projects.push(project);
+                }
+            }
+        }
+        self.tags
+            .extend(self.projects.iter().flat_map(|p| p.tags.clone()));
+        self.external = scan.external;
+        self.subprojects = scan.subprojects;
+        self.archived = scan.archived;
+        self.errors = scan.errors;
+        self.stats = scan.stats;
+        res
+    }
+    /// Manager over `projects` which only exist in memory, for driving the
+    /// sorting/filtering/tag logic without a root directory. Modifications
+    /// work as usual but nothing is written to disk.
+    pub fn from_projects(projects: Vec<Project>) -> Self {
+        let root = PathBuf::from("/");
+        let storage = Memory::new(&root);
+        for project in &projects {
+            let path = root.join(&project.name);
+            let _ = storage.create_dir(&path);
+            project.save(&storage, path).unwrap();
+        }
+        Self {
+            root,
+            tags: projects.iter().flat_map(|p| p.tags.clone()).collect(),
             projects,
-            tags,
+            archived: Vec::new(),
+            external: HashMap::new(),
+            subprojects: HashMap::new(),
+            errors: Vec::new(),
+            stats: ScanStats::default(),
+            normalize: false,
+            unlocked: false,
+            deferred: None,
+            scaffold: Vec::new(),
+            registry: Registry::in_memory(),
+            storage: Box::new(storage),
+        }
+    }
+    /// Problems found by the last `load` or `reload`: projects skipped because
+    /// their metadata was broken or their name was taken, and names which
+    /// only differ in case or unicode normalization.
+    pub fn load_errors(&self) -> &[LoadProblem] {
+        &self.errors
+    }
+    /// Directories read and time spent by the last `load` or `reload`.
+    pub fn scan_stats(&self) -> ScanStats {
+        self.stats
+    }
+    pub fn len(&self) -> usize {
+        self.projects.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty()
+    }
+    pub fn get_project(&self, name: &str) -> Option<&Project> {
+        self.projects.iter().find(|p| p.name == name)
+    }
+    /// Projects having `tag`, sorted by `order`.
+    pub fn with_tag(&self, tag: &str, order: SortOrder) -> Vec<Project> {
+        self.iter_projects(order)
+            .filter(|p| p.tags.contains(tag))
+            .cloned()
+            .collect()
+    }
+    /// Projects whose name or one of tags contains `query`, sorted by
+    /// `order`. Both are compared folded, see [`names::fold`].
+    pub fn search(&self, query: &str, order: SortOrder) -> Vec<Project> {
+        let query = names::fold(query);
+        self.iter_projects(order)
+            .filter(|p| {
+                names::fold(&p.name).contains(&query)
+                    || p.tags.iter().any(|t| names::fold(t).contains(&query))
+            })
+            .cloned()
+            .collect()
+    }
+    /// Name of the project containing current working directory.
+    pub fn current(&self) -> Result<String, String> {
+        let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+        let cwd = cwd.canonicalize().unwrap_or(cwd);
+        self.project_at(&cwd)
+            .ok_or_else(|| format!("Current directory({:?}) isn't inside a project", cwd))
+    }
+    /// Name of the project containing directory `dir`, which must be
+    /// canonical.
+    pub fn project_at(&self, dir: &Path) -> Option<String> {
+        // the deepest match wins so subprojects beat their parents
+        if let Some((name, _)) = self
+            .external
+            .iter()
+            .filter(|(_, path)| dir.starts_with(path))
+            .max_by_key(|(_, path)| path.components().count())
+        {
+            return Some(name.to_owned());
+        }
+        find_containing(self.storage.as_ref(), &self.root, dir).map(|(_, project)| project.name)
+    }
+    /// Name of the project containing `path`, which is relative to the
+    /// current directory unless it's absolute or starts with `~`.
+    fn resolve_path(&self, path: &str) -> Result<String, ProjectError> {
+        let expanded = match path.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()
+                .ok_or_else(|| String::from("Couldn't find home directory"))?
+                .join(rest.trim_start_matches('/')),
+            _ => PathBuf::from(path),
+        };
+        let dir = expanded
+            .canonicalize()
+            .map_err(|e| format!("Couldn't resolve {:?}: {}", path, e))?;
+        self.project_at(&dir)
+            .ok_or_else(|| format!("{:?} isn't inside a project", dir).into())
+    }
+    /// Name of the project with id `id`.
+    pub fn find_id(&self, id: &str) -> Result<String, ProjectError> {
+        self.projects
+            .iter()
+            .find(|p| p.id.as_deref() == Some(id))
+            .map(|p| p.name.clone())
+            .ok_or_else(|| {
+                ProjectError::new(
+                    ProjectErrorTypes::NonExistingProject,
+                    format!("No project has id {}", id),
+                )
+            })
+    }
+    /// Resolves a possibly partial project name. An exact match wins, then a
+    /// unique prefix match, then a unique substring match, compared folded
+    /// like [`names::fold`].
+    /// Ambiguous names produce an error listing the candidates. Names with a
+    /// `/` or starting with `~` are paths, resolved to the project containing
+    /// them.
+    pub fn resolve_name(&self, name: &str) -> Result<String, ProjectError> {
+        if self.projects.iter().any(|p| p.name == name) {
+            return Ok(name.to_owned());
+        }
+        if name.contains('/') || name.starts_with('~') {
+            return self.resolve_path(name);
+        }
+        let needle = names::fold(name);
+        let matchers: [&dyn Fn(&str) -> bool; 2] = [
+            &|n: &str| names::fold(n).starts_with(&needle),
+            &|n: &str| names::fold(n).contains(&needle),
+        ];
+        for matcher in matchers {
+            let mut candidates: Vec<&String> = self
+                .projects
+                .iter()
+                .map(|p| &p.name)
+                .filter(|n| matcher(n))
+                .collect();
+            match candidates.len() {
+                0 => continue,
+                1 => return Ok(candidates[0].to_owned()),
+                _ => {
+                    candidates.sort();
+                    return Err(ProjectError::from(format!(
+                        "'{}' matches several projects:\n  {}",
+                        name,
+                        candidates
+                            .iter()
+                            .map(|c| c.as_str())
+                            .collect::<Vec<_>>()
+                            .join("\n  ")
+                    )));
+                }
+            }
         }
+        Err(ProjectError::non_existing(
+            name,
+            self.projects.iter().map(|p| &p.name),
+        ))
+    }
+    pub fn get_root(&self) -> &Path {
+        &self.root
     }
     pub fn get_path(&self, name: &str) -> PathBuf {
-        self.root.join(name)
+        project_path(&self.root, &self.external, name)
     }
-    pub fn get_mut_project(&mut self, name: &str) -> Result<&mut Project, String> {
-        let project = self.projects.iter_mut().find(|p| p.name == name);
-        if project.is_none() {
-            return Err(format!("Such project({}) doesn't exist", name));
+    pub fn get_mut_project(&mut self, name: &str) -> Result<&mut Project, ProjectError> {
+        if !self.projects.iter().any(|p| p.name == name) {
+            return Err(ProjectError::non_existing(
+                name,
+                self.projects.iter().map(|p| &p.name),
+            ));
         }
 
-        Ok(project.unwrap())
+        Ok(self.projects.iter_mut().find(|p| p.name == name).unwrap())
+    }
+    /// Projects sorted by `order`, borrowed instead of cloned.
+    pub fn iter_projects(&self, order: SortOrder) -> impl Iterator<Item = &Project> {
+        self.iter_projects_by(&[order])
+    }
+    /// Projects sorted by the first of `keys`, ties broken by the next ones.
+    /// Name is always the last key so the order is the same on every run.
+    pub fn iter_projects_by(&self, keys: &[SortOrder]) -> impl Iterator<Item = &Project> {
+        let mut res: Vec<&Project> = self.projects.iter().collect();
+        res.sort_by(|a, b| {
+            keys.iter()
+                .chain([&SortOrder::Name])
+                .fold(Ordering::Equal, |ord, key| {
+                    ord.then_with(|| key.compare(a, b))
+                })
+        });
+        res.into_iter()
+    }
+    pub fn tags(&self) -> &HashSet<String> {
+        &self.tags
     }
+    /// Picks one of the projects `keep` accepts, weighted by the days since
+    /// they were accessed so neglected ones come up more often. `roll`, from
+    /// 0 up to(excluding) 1, decides which one is picked.
+    pub fn pick_weighted(&self, keep: impl Fn(&Project) -> bool, roll: f64) -> Option<&Project> {
+        let now = OffsetDateTime::now_utc();
+        let weighted: Vec<(f64, &Project)> = self
+            .iter_projects(SortOrder::Name)
+            .filter(|p| keep(p))
+            .map(|p| ((now - p.accessed).whole_days().max(0) as f64 + 1.0, p))
+            .collect();
+        let mut left = roll * weighted.iter().map(|(w, _)| w).sum::<f64>();
+        for (weight, project) in &weighted {
+            if left < *weight {
+                return Some(project);
+            }
+            left -= weight;
+        }
+        weighted.last().map(|(_, p)| *p)
+    }
+    /// Number of projects using each tag. Tags only added with `insert_tag`
+    /// aren't included.
+    pub fn tag_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for tag in self.projects.iter().flat_map(|p| &p.tags) {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+    #[deprecated(note = "use iter_projects, which doesn't clone every project")]
     pub fn get_projects(&self, order: SortOrder) -> Vec<Project> {
-        let mut res = self.projects.clone();
-        match order {
-            SortOrder::Creation => res.sort_by_key(|p| Reverse(p.created)),
-            SortOrder::AccessTime => res.sort_by_key(|p| Reverse(p.accessed)),
-            SortOrder::Name => res.sort_by_key(|p| p.name.clone()),
-        };
-        res
+        self.iter_projects(order).cloned().collect()
     }
+    #[deprecated(note = "use tags, which doesn't clone the set")]
     pub fn get_tags(&self) -> HashSet<String> {
         self.tags.clone()
     }
+    /// Keeps only projects for which `f` returns true. Directories of dropped
+    /// projects aren't touched.
+    pub fn retain<F>(&mut self, mut f: F) -> Result<(), String>
+    where
+        F: FnMut(&Project, PathBuf) -> Result<bool, String>,
+    {
+        let mut res = Ok(());
+        let (root, external) = (&self.root, &self.external);
+        self.projects
+            .retain(|p| match f(p, project_path(root, external, &p.name)) {
+                Ok(keep) => keep,
+                Err(e) => {
+                    res = Err(e);
+                    true
+                }
+            });
+        res
+    }
+    /// Lowercases tags of every project in memory, merging their case
+    /// variants, and keeps normalizing tags given to later operations.
+    /// Returns names and old tags of the changed projects, which aren't
+    /// saved until they're modified.
+    pub fn normalize_tags(&mut self) -> Vec<(String, HashSet<String>)> {
+        self.normalize = true;
+        let mut changed = Vec::new();
+        for project in &mut self.projects {
+            let tags = project.tags.iter().map(|t| t.to_lowercase()).collect();
+            if tags != project.tags {
+                changed.push((
+                    project.name.clone(),
+                    std::mem::replace(&mut project.tags, tags),
+                ));
+            }
+        }
+        self.tags = self.tags.iter().map(|t| t.to_lowercase()).collect();
+        changed
+    }
+    /// `tag` as it would be stored, according to `normalize_tags`.
+    pub fn normalize_tag(&self, tag: &str) -> String {
+        if self.normalize {
+            tag.to_lowercase()
+        } else {
+            tag.to_owned()
+        }
+    }
+    fn normalize_set(&self, tags: HashSet<String>) -> HashSet<String> {
+        tags.iter().map(|t| self.normalize_tag(t)).collect()
+    }
     pub fn insert_tag(&mut self, tag: String) {
-        self.tags.insert(tag);
+        self.tags.insert(self.normalize_tag(&tag));
+    }
+    /// Fails with a [`ProjectErrorTypes::Permission`] error if any of `paths`
+    /// isn't writable, so operations can check before changing anything.
+    fn check_writable(&self, paths: &[&Path]) -> Result<(), ProjectError> {
+        match paths.iter().find(|p| !self.storage.is_writable(p)) {
+            Some(path) => Err(ProjectError::permission(path)),
+            None => Ok(()),
+        }
     }
-    pub fn create(&mut self, project: Project) -> Result<(), String> {
-        if self.get_mut_project(&project.name).is_ok() {
+    pub fn create(&mut self, mut project: Project) -> Result<(), ProjectError> {
+        if project.name == ARCHIVE_DIR || project.name == TRASH_DIR {
             return Err(format!(
-                "A project with name '{}' already exists",
+                "'{}' is reserved for archived and deleted projects",
                 project.name
-            ));
+            )
+            .into());
         }
+        self.check_root_not_nested()?;
+        project.tags = self.normalize_set(project.tags);
+        self.check_name_free(&project.name, None)?;
         let path = self.get_path(&project.name);
-        if !path.is_dir() {
-            fs::create_dir(&path).unwrap();
+        self.check_writable(&[&self.root, &path])?;
+        init_dir(self.storage.as_ref(), &path, &project)?;
+        self.write_scaffold(&path, &project.name)?;
+        self.tags.extend(project.tags.clone());
+        self.projects.push(project);
+        Ok(())
+    }
+    /// Files(by path relative to the project) which `create` writes into new
+    /// projects, with `{name}` replaced by the name of the project.
+    pub fn with_scaffold(mut self, files: Vec<(PathBuf, String)>) -> Self {
+        self.scaffold = files;
+        self
+    }
+    /// Writes the scaffold files missing from project directory `path`.
+    fn write_scaffold(&self, path: &Path, name: &str) -> Result<(), String> {
+        for (relative, content) in &self.scaffold {
+            let file = path.join(relative);
+            if self.storage.is_file(&file) {
+                continue;
+            }
+            if let Some(parent) = file.parent() {
+                self.storage.create_dir_all(parent)?;
+            }
+            self.storage
+                .write(&file, &content.replace("{name}", name))?;
         }
-        let mut gitignore = fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(path.join(path.join(".gitignore")))
-            .unwrap();
-        writeln!(gitignore, "{}", PROJECT_FILE).unwrap();
-        project.save(path)?;
         Ok(())
     }
-    pub fn rename(&mut self, src: &str, dst: &str) -> Result<(), String> {
-        if self.get_mut_project(dst).is_ok() {
-            return Err(format!("A project with name '{}' already exists", dst));
+    /// Moves(or copies if `copy` is set) directory `src` into root and turns
+    /// it into a project. The directory is put back if that fails.
+    pub fn create_from(
+        &mut self,
+        project: Project,
+        src: &Path,
+        copy: bool,
+    ) -> Result<(), ProjectError> {
+        if !self.storage.is_dir(src) {
+            return Err(format!("{:?} isn't a directory", src).into());
         }
-
-        let idx = self.projects.iter().position(|p| p.name == src).unwrap();
-        let mut project = self.projects.remove(idx);
+        if self.storage.is_file(&src.join(PROJECT_FILE)) {
+            return Err(format!("{:?} is already a project", src).into());
+        }
+        self.check_root_not_nested()?;
+        self.check_name_free(&project.name, None)?;
+        let path = self.get_path(&project.name);
+        if self.storage.is_dir(&path) {
+            return Err(format!("{:?} already exists", path).into());
+        }
+        self.check_writable(&[&self.root])?;
+        if copy {
+            self.storage.copy_dir(src, &path)?;
+        } else {
+            self.check_writable(&[src.parent().unwrap_or(src)])?;
+            self.storage
+                .rename(src, &path)
+                .map_err(|e| format!("{}\nUse --copy if it's on a different filesystem", e))?;
+        }
+        self.create(project).inspect_err(|_| {
+            let _ = match copy {
+                true => self.storage.remove_dir(&path),
+                false => self.storage.rename(&path, src),
+            };
+        })
+    }
+    /// New projects would end up nested in another project if root is one or
+    /// is inside one.
+    fn check_root_not_nested(&self) -> Result<(), ProjectError> {
+        match enclosing_project(self.storage.as_ref(), &self.root) {
+            Some(project) => Err(nested_root(&self.root, &project)),
+            None => Ok(()),
+        }
+    }
+    /// Parent of subproject `name`, see [`Project::get_subprojects`].
+    pub fn parent(&self, name: &str) -> Option<&String> {
+        self.subprojects.get(name)
+    }
+    /// Fails if a project other than `except` is named `name`, or a name which
+    /// is the same on case-insensitive filesystems.
+    fn check_name_free(&self, name: &str, except: Option<&str>) -> Result<(), ProjectError> {
+        let key = names::fold(name);
+        match self
+            .projects
+            .iter()
+            .map(|p| &p.name)
+            .filter(|other| Some(other.as_str()) != except)
+            .find(|other| names::fold(other) == key)
+        {
+            Some(other) if *other == name => Err(ProjectError::new(
+                ProjectErrorTypes::DuplicateName,
+                format!("A project with name '{}' already exists", name),
+            )),
+            Some(other) => Err(ProjectError::name_collision(name, other)),
+            None => Ok(()),
+        }
+    }
+    /// Lets later operations change locked projects, for `--unlock`.
+    pub fn unlock(&mut self) {
+        self.unlocked = true;
+    }
+    pub fn check_unlocked(&mut self, name: &str) -> Result<(), ProjectError> {
+        match self.get_mut_project(name)?.locked && !self.unlocked {
+            true => Err(ProjectError::locked(name)),
+            false => Ok(()),
+        }
+    }
+    pub fn set_locked(&mut self, name: &str, locked: bool) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.get_mut_project(name)?.locked = locked;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    /// Replaces the title and custom values of project `name` with what
+    /// `encrypt` makes of them(as JSON), so they aren't readable at rest.
+    pub fn seal(
+        &mut self,
+        name: &str,
+        encrypt: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        let project = self.get_mut_project(name)?;
+        if project.sealed.is_some() {
+            return Err(format!("'{}' is already encrypted", name).into());
+        }
+        let secrets = Secrets {
+            title: project.title.clone(),
+            custom: project.custom.clone(),
+        };
+        project.sealed = Some(encrypt(&serde_json::to_string(&secrets).unwrap())?);
+        project.title = None;
+        project.custom.clear();
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    /// Undoes [`ProjectManager::seal`], storing the title and custom values
+    /// in plain text again.
+    pub fn unseal(
+        &mut self,
+        name: &str,
+        decrypt: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        let project = self.get_mut_project(name)?;
+        *project = project.unsealed(decrypt)?;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    fn check_not_subproject(&self, name: &str) -> Result<(), ProjectError> {
+        match self.subprojects.get(name) {
+            Some(parent) => Err(format!(
+                "'{}' is a subproject of '{}', change subprojects in its metadata instead",
+                name, parent
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+    /// Turns `path`, which may be outside root, into a project. Directories
+    /// outside root are remembered in the registry of external projects and
+    /// ones inside another project become its subprojects.
+    pub fn register(&mut self, mut project: Project, path: PathBuf) -> Result<(), ProjectError> {
+        project.tags = self.normalize_set(project.tags);
+        self.check_name_free(&project.name, None)?;
+        if self.storage.is_file(&path.join(PROJECT_FILE)) {
+            return Err(format!("{:?} is already a project", path).into());
+        }
+        if path.parent() == Some(self.root.as_path()) && path.ends_with(&project.name) {
+            return self.create(project);
+        }
+        self.check_writable(&[&path])?;
+        let parent = self
+            .projects
+            .iter()
+            .filter(|p| !self.subprojects.contains_key(&p.name))
+            .map(|p| (p.name.clone(), self.get_path(&p.name)))
+            .find(|(_, dir)| path.starts_with(dir) && path != *dir);
+        if let Some((parent, dir)) = parent {
+            let relative = path.strip_prefix(&dir).unwrap().to_owned();
+            init_dir(self.storage.as_ref(), &path, &project)?;
+            self.get_mut_project(&parent)?.subprojects.push(relative);
+            self.save_changed(&parent, dir)?;
+            self.subprojects.insert(project.name.clone(), parent);
+            self.external.insert(project.name.clone(), path);
+            self.tags.extend(project.tags.clone());
+            self.projects.push(project);
+            return Ok(());
+        }
+        init_dir(self.storage.as_ref(), &path, &project)?;
+        self.registry.add(&self.root, path.clone())?;
+        self.external.insert(project.name.clone(), path);
+        self.tags.extend(project.tags.clone());
+        self.projects.push(project);
+        Ok(())
+    }
+    /// Renames project `src` and its directory to `dst`. Fails with a
+    /// `DuplicateName` error if another project or directory already has
+    /// that name.
+    pub fn rename(&mut self, src: &str, dst: &str) -> Result<(), ProjectError> {
+        self.check_not_subproject(src)?;
+        self.check_unlocked(src)?;
+        self.check_name_free(dst, Some(src))?;
 
         let path: PathBuf = self.get_path(src);
         let mut new_path = path.clone();
         new_path.pop();
+        self.check_writable(&[&new_path, &path.join(PROJECT_FILE)])?;
         new_path = new_path.join(dst);
+        // changing only the case may find the same directory
+        if names::fold(src) != names::fold(dst) && self.storage.is_dir(&new_path) {
+            return Err(ProjectError::new(
+                ProjectErrorTypes::DuplicateName,
+                format!("{:?} already exists", new_path),
+            ));
+        }
 
-        fs::rename(path.clone(), &new_path)
-            .unwrap_or_else(|e| panic!("Couldn't rename {:?} to {:?}.\n{}", &path, &new_path, e));
+        self.storage.rename(&path, &new_path)?;
+        let idx = self.projects.iter().position(|p| p.name == src).unwrap();
+        let mut project = self.projects.remove(idx);
         project.rename(dst.to_string());
-        project.save(new_path)?;
+        project.modified = Some(OffsetDateTime::now_utc());
+        project.save(self.storage.as_ref(), new_path.clone())?;
+        if self.external.remove(src).is_some() {
+            self.registry.remove(&self.root, &path)?;
+            self.registry.add(&self.root, new_path.clone())?;
+            self.external.insert(dst.to_string(), new_path.clone());
+        }
+        for (name, parent) in &mut self.subprojects {
+            if parent == src {
+                *parent = dst.to_string();
+                let dir = self.external.get_mut(name).unwrap();
+                *dir = new_path.join(dir.strip_prefix(&path).unwrap());
+            }
+        }
+        self.projects.push(project);
+        Ok(())
+    }
+    /// Checks that every `(old, new)` rename of a batch can be done before any
+    /// of them is: new names must be valid, distinct(compared folded) and
+    /// free, and the projects neither subprojects nor locked.
+    pub fn check_renames(&self, renames: &[(String, String)]) -> Result<(), ProjectError> {
+        let mut problems = Vec::new();
+        for (i, (old, new)) in renames.iter().enumerate() {
+            let project = self.get_project(old).ok_or_else(|| {
+                ProjectError::non_existing(old, self.projects.iter().map(|p| &p.name))
+            })?;
+            let taken = self
+                .projects
+                .iter()
+                .find(|p| p.name != *old && names::fold(&p.name) == names::fold(new));
+            if new.is_empty() || new.contains('/') {
+                problems.push(format!("'{}' can't be used as a name", new));
+            } else if renames[..i]
+                .iter()
+                .any(|(_, other)| names::fold(other) == names::fold(new))
+            {
+                problems.push(format!("more than one project would be named '{}'", new));
+            } else if let Some(other) = taken {
+                problems.push(format!("a project named '{}' already exists", other.name));
+            } else if let Some(parent) = self.parent(old) {
+                problems.push(format!("'{}' is a subproject of '{}'", old, parent));
+            } else if project.locked && !self.unlocked {
+                problems.push(format!(
+                    "'{}' is locked, pass --unlock to rename it anyway",
+                    old
+                ));
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(format!("Nothing was renamed:\n{}", problems.join("\n")).into()),
+        }
+    }
+    /// Moves the files of project `src` into project `dst`, which also gets
+    /// the tags of `src`, and moves what's left of `src` to the trash. If
+    /// `dst` is a directory next to `src` instead, its files are moved into
+    /// `src` which is then renamed to `dst`. Nothing is moved if both have a
+    /// file of the same name.
+    pub fn merge(&mut self, src: &str, dst: &str) -> Result<(), ProjectError> {
+        self.check_not_subproject(src)?;
+        self.check_unlocked(src)?;
+        let src_path = self.get_path(src);
+        let target = self.get_project(dst).cloned();
+        let dst_path = match &target {
+            Some(_) => {
+                self.check_unlocked(dst)?;
+                self.get_path(dst)
+            }
+            None => src_path.with_file_name(dst),
+        };
+        let (from, to) = match target {
+            Some(_) => (&src_path, &dst_path),
+            None => (&dst_path, &src_path),
+        };
+        self.check_writable(&[from, to, &self.root])?;
+        // files both have with the same content, like the .gitignore of new
+        // projects, are only kept once
+        let same = |a: &Path, b: &Path| {
+            self.storage.is_file(a)
+                && self.storage.is_file(b)
+                && matches!(
+                    (self.storage.read(a), self.storage.read(b)),
+                    (Ok(a), Ok(b)) if a == b
+                )
+        };
+        let (entries, duplicates): (Vec<PathBuf>, Vec<PathBuf>) = self
+            .storage
+            .list(from)?
+            .into_iter()
+            .filter(|p| !p.ends_with(PROJECT_FILE))
+            .partition(|p| !same(p, &to.join(p.file_name().unwrap())));
+        if let Some(clash) = entries.iter().find(|e| {
+            let other = to.join(e.file_name().unwrap());
+            self.storage.is_file(&other) || self.storage.is_dir(&other)
+        }) {
+            return Err(format!(
+                "Both have {:?}, merge them by hand",
+                clash.file_name().unwrap()
+            )
+            .into());
+        }
+        if let Some(target) = &target {
+            let mut tags = target.get_tags();
+            tags.extend(self.get_project(src).unwrap().get_tags());
+            self.modify(dst, tags)?;
+        }
+        for entry in entries {
+            self.storage
+                .rename(&entry, &to.join(entry.file_name().unwrap()))?;
+        }
+        for duplicate in duplicates {
+            self.storage.remove_file(&duplicate)?;
+        }
+        match target {
+            Some(_) => self.delete(src),
+            None => {
+                if !self.storage.list(&dst_path)?.is_empty() {
+                    return Err(format!("Couldn't remove {:?}: not empty", dst_path).into());
+                }
+                self.storage.remove_dir(&dst_path)?;
+                self.rename(src, dst)
+            }
+        }
+    }
+    /// Metadata files of every project and archived project by directory, as
+    /// they are stored.
+    pub fn metadata_files(&self) -> BTreeMap<PathBuf, String> {
+        let archived = self
+            .archived
+            .iter()
+            .map(|p| self.root.join(ARCHIVE_DIR).join(&p.name));
+        self.projects
+            .iter()
+            .map(|p| self.get_path(&p.name))
+            .chain(archived)
+            .filter_map(|dir| {
+                let data = self.storage.read(&dir.join(PROJECT_FILE)).ok()?;
+                Some((dir, data))
+            })
+            .collect()
+    }
+    /// Archived projects, which aren't part of any other listing.
+    pub fn archived(&self) -> &[Project] {
+        &self.archived
+    }
+    /// Moves project `name` into the archive directory of root, as a
+    /// compressed tarball if `compress` is set. Only projects inside root can
+    /// be archived.
+    pub fn archive(&mut self, name: &str, compress: bool) -> Result<(), ProjectError> {
+        self.check_unlocked(name)?;
+        self.check_not_subproject(name)?;
+        if self.external.contains_key(name) {
+            return Err(format!("'{}' is outside root and can't be archived", name).into());
+        }
+        let path = self.get_path(name);
+        let archive = self.root.join(ARCHIVE_DIR);
+        self.check_writable(&[&self.root, &path, &archive])?;
+        let dest = archive.join(name);
+        if self.storage.is_dir(&dest) {
+            return Err(format!("{:?} already exists", dest).into());
+        }
+        if !self.storage.is_dir(&archive) {
+            self.storage.create_dir(&archive)?;
+        }
+        let idx = self.projects.iter().position(|p| p.name == name).unwrap();
+        let mut project = self.projects[idx].clone();
+        if compress {
+            self.storage.create_dir(&dest)?;
+            if let Err(e) = self.storage.compress(&path, &dest.join(ARCHIVE_FILE)) {
+                self.storage.remove_dir(&dest)?;
+                return Err(e.into());
+            }
+            self.storage.remove_dir(&path)?;
+            project.archived = Some(Archived::Compressed);
+        } else {
+            self.storage.rename(&path, &dest)?;
+            project.archived = Some(Archived::Moved);
+        }
+        project.save(self.storage.as_ref(), dest)?;
+        self.projects.remove(idx);
+        self.archived.push(project);
+        self.drop_subprojects(name);
+        Ok(())
+    }
+    /// Moves archived project `name` back into root.
+    pub fn restore(&mut self, name: &str) -> Result<(), ProjectError> {
+        let idx = self
+            .archived
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| {
+                ProjectError::non_existing(name, self.archived.iter().map(|p| &p.name))
+            })?;
+        if self.archived[idx].locked && !self.unlocked {
+            return Err(ProjectError::locked(name));
+        }
+        self.check_name_free(name, None)?;
+        let path = self.root.join(name);
+        let src = self.root.join(ARCHIVE_DIR).join(name);
+        self.check_writable(&[&self.root, &self.root.join(ARCHIVE_DIR), &src])?;
+        let mut project = self.archived[idx].clone();
+        match project.archived {
+            Some(Archived::Compressed) => {
+                self.storage.create_dir(&path)?;
+                self.storage.extract(&src.join(ARCHIVE_FILE), &path)?;
+                self.storage.remove_dir(&src)?;
+            }
+            _ => self.storage.rename(&src, &path)?,
+        }
+        project.archived = None;
+        project.save(self.storage.as_ref(), path)?;
+        self.archived.remove(idx);
+        self.tags.extend(project.tags.clone());
+        self.projects.push(project);
+        Ok(())
+    }
+    /// Moves project `name` into the trash of root, see [`crate::trash`].
+    /// External projects are forgotten until they're restored.
+    pub fn delete(&mut self, name: &str) -> Result<(), ProjectError> {
+        self.check_unlocked(name)?;
+        self.check_not_subproject(name)?;
+        let path = self.get_path(name);
+        let trash_dir = self.root.join(TRASH_DIR);
+        self.check_writable(&[path.parent().unwrap_or(&path), &self.root, &trash_dir])?;
+        let mut entries = trash::entries(self.storage.as_ref(), &self.root)?;
+        let entry = trash::Entry::new(name, path.clone());
+        let dest = trash::dir(self.storage.as_ref(), &self.root)?.join(&entry.id);
+        self.storage.rename(&path, &dest)?;
+        entries.push(entry);
+        trash::save(self.storage.as_ref(), &self.root, &entries)?;
+        if self.external.remove(name).is_some() {
+            self.registry.remove(&self.root, &path)?;
+        }
+        self.projects.retain(|p| p.name != name);
+        self.drop_subprojects(name);
+        Ok(())
+    }
+    /// Forgets subprojects of `parent` after its directory was moved away.
+    fn drop_subprojects(&mut self, parent: &str) {
+        let names: Vec<String> = self
+            .subprojects
+            .iter()
+            .filter(|(_, p)| *p == parent)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.subprojects.remove(&name);
+            self.external.remove(&name);
+            self.projects.retain(|p| p.name != name);
+        }
+    }
+    /// Deleted projects which are still in the trash, oldest first.
+    pub fn trashed(&self) -> Result<Vec<trash::Entry>, String> {
+        trash::entries(self.storage.as_ref(), &self.root)
+    }
+    /// Moves the most recently deleted project called `name` back to where it
+    /// was deleted from.
+    pub fn restore_trashed(&mut self, name: &str) -> Result<(), ProjectError> {
+        let mut entries = self.trashed()?;
+        let idx = entries
+            .iter()
+            .rposition(|e| e.name == name)
+            .ok_or_else(|| ProjectError::non_existing(name, entries.iter().map(|e| &e.name)))?;
+        self.check_name_free(name, None)?;
+        let entry = entries.remove(idx);
+        if self.storage.is_dir(&entry.path) {
+            return Err(format!("{:?} already exists", entry.path).into());
+        }
+        let src = self.root.join(TRASH_DIR).join(&entry.id);
+        let parent = entry.path.parent().unwrap_or(&entry.path);
+        self.check_writable(&[&self.root.join(TRASH_DIR), parent])?;
+        let project = self
+            .storage
+            .read(&src.join(PROJECT_FILE))
+            .and_then(|data| serde_json::from_str::<Project>(&data).map_err(|e| e.to_string()))?;
+        self.storage.rename(&src, &entry.path)?;
+        trash::save(self.storage.as_ref(), &self.root, &entries)?;
+        if entry.path != self.root.join(name) {
+            self.registry.add(&self.root, entry.path.clone())?;
+            self.external.insert(project.name.clone(), entry.path);
+        }
+        self.tags.extend(self.normalize_set(project.tags.clone()));
         self.projects.push(project);
         Ok(())
     }
-    pub fn modify(&mut self, name: &str, tags: HashSet<String>) -> Result<(), String> {
+    /// Permanently removes projects deleted longer than `keep` ago, or all of
+    /// them if it's None. Returns the removed entries.
+    pub fn empty_trash(
+        &mut self,
+        keep: Option<Duration>,
+    ) -> Result<Vec<trash::Entry>, ProjectError> {
+        let (expired, entries): (Vec<_>, Vec<_>) = self
+            .trashed()?
+            .into_iter()
+            .partition(|e| keep.is_none_or(|keep| e.expired(keep)));
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        self.check_writable(&[&self.root.join(TRASH_DIR)])?;
+        for entry in &expired {
+            let dir = self.root.join(TRASH_DIR).join(&entry.id);
+            if self.storage.is_dir(&dir) {
+                self.storage.remove_dir(&dir)?;
+            }
+        }
+        trash::save(self.storage.as_ref(), &self.root, &entries)?;
+        Ok(expired)
+    }
+    pub fn modify(&mut self, name: &str, tags: HashSet<String>) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        let tags = self.normalize_set(tags);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.modify(tags);
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    pub fn set_priority(&mut self, name: &str, priority: Option<u8>) -> Result<(), ProjectError> {
         let path: PathBuf = self.get_path(name);
-        let project = self.get_mut_project(name)?;
-        project.modify(tags);
-        project.save(path)?;
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.priority = priority;
+        self.save_changed(name, path)?;
         Ok(())
     }
-    pub fn exec(mut self, name: &str, default_executor: String, cmd: &str) -> Result<(), String> {
-        let mut cmd = cmd;
+    pub fn set_owner(&mut self, name: &str, owner: Option<String>) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.owner = owner;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    pub fn set_title(&mut self, name: &str, title: Option<String>) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.title = title;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    /// Remembers `cmd` as the last command run in project `name`. Like the
+    /// accessed time, it's kept even for locked projects.
+    pub fn set_last_command(&mut self, name: &str, cmd: &str) -> Result<(), ProjectError> {
         let path: PathBuf = self.get_path(name);
         let project = self.get_mut_project(name)?;
-
-        project.accessed = OffsetDateTime::now_utc();
-        project.save(path.clone())?;
+        if project.last_command.as_deref() == Some(cmd) {
+            return Ok(());
+        }
+        project.last_command = Some(cmd.to_owned());
+        self.save(name, path)?;
+        Ok(())
+    }
+    pub fn set_hidden(&mut self, name: &str, hidden: bool) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.hidden = hidden;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    pub fn set_completed(
+        &mut self,
+        name: &str,
+        completed: Option<Date>,
+    ) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.completed = completed;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    pub fn set_due(&mut self, name: &str, due: Option<Date>) -> Result<(), ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.check_unlocked(name)?;
+        self.get_mut_project(name)?.due = due;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    /// Checks out `branch` of project `name` into a new git worktree at
+    /// `path` and remembers it in the metadata. The branch is created from
+    /// the current HEAD if it doesn't exist.
+    pub fn add_worktree(
+        &mut self,
+        name: &str,
+        branch: &str,
+        path: PathBuf,
+    ) -> Result<(), ProjectError> {
+        let repo = self.get_path(name);
+        if self.get_mut_project(name)?.worktrees.contains_key(branch) {
+            return Err(format!("'{}' already has a worktree for {}", name, branch).into());
+        }
+        if let Some(parent) = path.parent() {
+            self.storage.create_dir_all(parent)?;
+        }
+        git::add_worktree(&repo, branch, &path)?;
+        self.get_mut_project(name)?
+            .worktrees
+            .insert(branch.to_owned(), path);
+        self.save_changed(name, repo)?;
+        Ok(())
+    }
+    /// Replaces metadata of project `name` with `project`, which must have
+    /// the same name(renaming moves the directory, see `rename`). Locked
+    /// projects are refused unless [`unlock`](Self::unlock) was called.
+    pub fn replace(&mut self, name: &str, mut project: Project) -> Result<(), ProjectError> {
+        if project.name != name {
+            return Err(format!("Name can't be changed here, rename '{}' instead", name).into());
+        }
+        self.check_unlocked(name)?;
+        let path: PathBuf = self.get_path(name);
+        project.tags = self.normalize_set(project.tags);
+        self.tags.extend(project.tags.clone());
+        *self.get_mut_project(name)? = project;
+        self.save_changed(name, path)?;
+        Ok(())
+    }
+    /// Changes the tags of many projects at once, saving them with
+    /// [`save_all`](Self::save_all). Nothing changes if any of them is locked.
+    pub fn modify_all(
+        &mut self,
+        changes: Vec<(String, HashSet<String>)>,
+    ) -> Result<(), ProjectError> {
+        for (name, _) in &changes {
+            self.check_unlocked(name)?;
+        }
+        let mut names = Vec::new();
+        for (name, tags) in changes {
+            let tags = self.normalize_set(tags);
+            let project = self.get_mut_project(&name)?;
+            project.modify(tags);
+            project.modified = Some(OffsetDateTime::now_utc());
+            names.push(name);
+        }
+        self.save_all(&names)
+    }
+    /// Writes the metadata of projects `names` from up to [`SAVE_THREADS`]
+    /// threads, for batch operations touching many projects. Every project
+    /// is tried and the failures are reported together.
+    pub fn save_all(&mut self, names: &[String]) -> Result<(), ProjectError> {
+        let mut failures = Vec::new();
+        let mut files = Vec::new();
+        for name in names {
+            let path = self.get_path(name);
+            if let Err(e) = self.check_writable(&[&path, &path.join(PROJECT_FILE)]) {
+                failures.push(format!("{}: {}", name, e));
+                continue;
+            }
+            if let Some(deferred) = &mut self.deferred {
+                deferred.remove(name);
+            }
+            let project = self.get_mut_project(name)?;
+            project.id.get_or_insert_with(new_id);
+            files.push((
+                name,
+                path.join(PROJECT_FILE),
+                serde_json::to_string(project).unwrap(),
+            ));
+        }
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(SAVE_THREADS);
+        let storage = self.storage.as_ref();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = files
+                .chunks(files.len().div_ceil(threads).max(1))
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|(name, file, data)| {
+                                let res = storage.write(file, data);
+                                res.err().map(|e| format!("{}: {}", name, e))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for worker in workers {
+                failures.extend(worker.join().unwrap());
+            }
+        });
+        if failures.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Couldn't save {} of {} project(s):\n  {}",
+            failures.len(),
+            names.len(),
+            failures.join("\n  ")
+        )
+        .into())
+    }
+    /// Saves project `name` after a change of its metadata, which becomes its
+    /// modified time.
+    fn save_changed(&mut self, name: &str, path: PathBuf) -> Result<(), ProjectError> {
+        self.get_mut_project(name)?.modified = Some(OffsetDateTime::now_utc());
+        self.save(name, path)
+    }
+    fn save(&mut self, name: &str, path: PathBuf) -> Result<(), ProjectError> {
+        self.check_writable(&[&path, &path.join(PROJECT_FILE)])?;
+        if let Some(deferred) = &mut self.deferred {
+            deferred.remove(name);
+        }
+        let project = self.projects.iter_mut().find(|p| p.name == name).unwrap();
+        project.id.get_or_insert_with(new_id);
+        Ok(project.save(self.storage.as_ref(), path)?)
+    }
+    /// Keeps accessed times set by `touch` and `set_accessed` in memory until
+    /// `flush`(or until the manager is dropped), so touching projects over
+    /// and over writes each metadata file once.
+    pub fn batch_access(&mut self) {
+        self.deferred.get_or_insert_with(HashSet::new);
+    }
+    /// Writes the accessed times kept since `batch_access`. Metadata is read
+    /// again and only its accessed time is updated(to the later of both), so
+    /// changes other processes made in the meantime aren't lost.
+    pub fn flush(&mut self) -> Result<(), ProjectError> {
+        let names = match &mut self.deferred {
+            Some(deferred) => std::mem::take(deferred),
+            None => return Ok(()),
+        };
+        let mut res = Ok(());
+        for name in names {
+            // renamed or deleted since, which saved it already
+            let project = match self.projects.iter().find(|p| p.name == name) {
+                Some(project) => project,
+                None => continue,
+            };
+            let path = self.get_path(&name);
+            let on_disk = self
+                .storage
+                .read(&path.join(PROJECT_FILE))
+                .ok()
+                .and_then(|data| serde_json::from_str::<Project>(&data).ok());
+            let project = match on_disk {
+                Some(mut on_disk) if on_disk.name == name => {
+                    on_disk.accessed = on_disk.accessed.max(project.accessed);
+                    on_disk
+                }
+                _ => project.clone(),
+            };
+            let saved = self
+                .check_writable(&[&path, &path.join(PROJECT_FILE)])
+                .and_then(|_| Ok(project.save(self.storage.as_ref(), path)?));
+            if res.is_ok() {
+                res = saved;
+            }
+        }
+        res
+    }
+    /// Marks the project as accessed now and returns its path.
+    pub fn touch(&mut self, name: &str) -> Result<PathBuf, ProjectError> {
+        self.set_accessed(name, OffsetDateTime::now_utc())
+    }
+    /// Marks the project as accessed at `time` and returns its path.
+    pub fn set_accessed(
+        &mut self,
+        name: &str,
+        time: OffsetDateTime,
+    ) -> Result<PathBuf, ProjectError> {
+        let path: PathBuf = self.get_path(name);
+        self.get_mut_project(name)?.accessed = time;
+        if let Some(deferred) = &mut self.deferred {
+            deferred.insert(name.to_owned());
+            return Ok(path);
+        }
+        self.save(name, path.clone())?;
+        Ok(path)
+    }
+    pub fn exec(self, name: &str, default_executor: String, cmd: &str) -> Result<(), ProjectError> {
+        self.exec_in(name, None, None, default_executor, cmd)
+    }
+    /// Like `exec`, but runs inside the worktree of `branch` if one is given
+    /// and in directory `subdir` of the project(or worktree), see
+    /// [`subdirectory`].
+    pub fn exec_in(
+        mut self,
+        name: &str,
+        worktree: Option<&str>,
+        subdir: Option<&Path>,
+        default_executor: String,
+        cmd: &str,
+    ) -> Result<(), ProjectError> {
+        let mut cmd = cmd;
+        let project_path = self.get_path(name);
+        if self.get_project(name).is_some() && !self.storage.is_dir(&project_path) {
+            return Err(ProjectError::missing_dir(name, &project_path));
+        }
+        let mut path = self.touch(name)?;
+        if let Some(branch) = worktree {
+            path = self
+                .get_mut_project(name)?
+                .worktrees
+                .get(branch)
+                .cloned()
+                .ok_or_else(|| format!("'{}' has no worktree for {}", name, branch))?;
+        }
+        if let Some(subdir) = subdir {
+            path = subdirectory(&path, subdir)?;
+        }
 
         // we will start a program in project directory and this current
         // rust program might need to wait until the program finishes. so
@@ -216,16 +2084,385 @@ impl ProjectManager {
         if cmd.is_empty() {
             cmd = &default_executor;
         }
-        let cmd = cmd.replace("{}", &path.to_string_lossy());
-        let cmd: Vec<&str> = cmd.split(' ').collect();
-        Command::new(cmd[0])
-            .args(&cmd[1..])
-            .current_dir(&path)
+        // it may have been removed while the metadata was saved
+        if !path.is_dir() {
+            return Err(ProjectError::missing_dir(name, &path));
+        }
+        command(&path, cmd)
             .spawn()
-            .unwrap()
-            .wait()
-            .unwrap();
+            .and_then(|mut child| child.wait())
+            .map_err(|e| format!("Couldn't run '{}': {}", cmd, e))?;
+
+        Ok(())
+    }
+    /// Runs `cmd`(or `default_executor` if it's empty) for all projects
+    /// `names` at once. With a `{paths}` argument it runs a single time in the
+    /// root, getting the path of every project in its place. Otherwise it
+    /// runs in each project at the same time, like one editor window per
+    /// project. Waits until all of them exit.
+    pub fn exec_many(
+        mut self,
+        names: &[String],
+        default_executor: String,
+        cmd: &str,
+    ) -> Result<(), ProjectError> {
+        let cmd = match cmd.is_empty() {
+            true => default_executor,
+            false => cmd.to_owned(),
+        };
+        for name in names {
+            let path = self.get_path(name);
+            if !self.storage.is_dir(&path) {
+                return Err(ProjectError::missing_dir(name, &path));
+            }
+        }
+        let paths = names
+            .iter()
+            .map(|name| self.touch(name))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        let root = self.root.clone();
+        // like in `exec_in`, the projects aren't needed while waiting
+        drop(self);
 
+        let err = |e: std::io::Error| format!("Couldn't run '{}': {}", cmd, e);
+        if cmd.split(' ').any(|arg| arg == "{paths}") {
+            let mut args = cmd.split(' ');
+            let program = args.next().unwrap_or_default();
+            let mut command = Command::new(program);
+            for arg in args {
+                match arg {
+                    "{paths}" => command.args(&paths),
+                    _ => command.arg(arg),
+                };
+            }
+            command.current_dir(root).status().map_err(err)?;
+            return Ok(());
+        }
+        let children = paths
+            .iter()
+            .map(|path| command(path, &cmd).spawn())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(err)?;
+        for mut child in children {
+            child.wait().map_err(err)?;
+        }
+        Ok(())
+    }
+    /// Starts watching the root and external projects for changes, see
+    /// [`crate::watch`].
+    #[cfg(feature = "watch")]
+    pub fn watch(&self) -> Result<crate::watch::Watcher, String> {
+        crate::watch::Watcher::new(&self.root, &self.external)
+    }
+    /// Loads projects on tokio's blocking thread pool so scanning a large root
+    /// doesn't block the async runtime.
+    #[cfg(feature = "async")]
+    pub async fn load_async(path: PathBuf) -> Result<Self, String> {
+        tokio::task::spawn_blocking(move || Self::load(path))
+            .await
+            .map_err(|e| format!("Couldn't load projects: {}", e))?
+            .map_err(String::from)
+    }
+    /// Like `exec` but waits for the command without blocking the runtime.
+    /// Unlike `exec` the manager is kept so long-running front-ends can reuse it.
+    #[cfg(feature = "async")]
+    pub async fn exec_async(
+        &mut self,
+        name: &str,
+        default_executor: &str,
+        cmd: &str,
+    ) -> Result<(), String> {
+        let path = self.touch(name)?;
+        let cmd = if cmd.is_empty() {
+            default_executor
+        } else {
+            cmd
+        };
+        tokio::process::Command::from(command(&path, cmd))
+            .status()
+            .await
+            .map_err(|e| format!("Couldn't run '{}': {}", cmd, e))?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    #[test]
+    fn every_saved_field_parses_again() {
+        let mut project = Project::new(
+            "alpha".into(),
+            datetime!(2025-06-01 9:00 UTC),
+            HashSet::from(["rust".to_owned()]),
+        );
+        project.modified = Some(datetime!(2025-06-30 18:30 UTC));
+        project.completed = Some(date!(2025 - 07 - 01));
+        project.title = Some("Alpha".into());
+        project.due = Some(date!(2025 - 08 - 01));
+        project.archived = Some(Archived::Moved);
+        project.priority = Some(3);
+        project.worktrees.insert("main".into(), "/wt/alpha".into());
+        project.subprojects.push("crates/core".into());
+        project.owner = Some("amin".into());
+        project.locked = true;
+        project.hidden = true;
+        project.sealed = Some("secret".into());
+        project.last_command = Some("cargo test".into());
+        project
+            .custom
+            .insert("team".into(), serde_json::json!("tools"));
+        let data = serde_json::to_string(&project).unwrap();
+        assert!(parse_metadata(&data).unwrap() == project);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let data = r#"{"name": "alpha", "created": "2025-06-01T09:00:00Z",
+            "accessed": "2025-06-01T09:00:00Z", "tags": [], "colour": "red"}"#;
+        assert_eq!(
+            parse_metadata(data).err().as_deref(),
+            Some("Unknown field 'colour'")
+        );
+    }
+
+    #[test]
+    fn saved_metadata_parses_again() {
+        let mut project = Project::new(
+            "alpha".into(),
+            datetime!(2025-06-01 9:00 UTC),
+            HashSet::new(),
+        );
+        project.modified = Some(datetime!(2025-06-30 18:30 UTC));
+        project.completed = Some(date!(2025 - 07 - 01));
+        let data = serde_json::to_string(&project).unwrap();
+        assert!(data.contains("modified") && data.contains("completed"));
+        let parsed = parse_metadata(&data).unwrap();
+        assert_eq!(parsed.get_modified(), project.get_modified());
+        assert_eq!(parsed.get_completed(), project.get_completed());
+    }
+
+    fn manager(names: &[&str]) -> ProjectManager {
+        ProjectManager::from_projects(
+            names
+                .iter()
+                .map(|n| {
+                    Project::new(
+                        n.to_string(),
+                        datetime!(2025-06-01 9:00 UTC),
+                        HashSet::new(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn exact_name_wins_over_prefix() {
+        let manager = manager(&["web", "website"]);
+        assert_eq!(manager.resolve_name("web").unwrap(), "web");
+    }
+
+    #[test]
+    fn unique_prefix_resolves() {
+        let manager = manager(&["alpha", "Beta"]);
+        assert_eq!(manager.resolve_name("be").unwrap(), "Beta");
+    }
+
+    #[test]
+    fn folded_names_resolve() {
+        let manager = manager(&["Straße", "Caf\u{e9}"]);
+        assert_eq!(manager.resolve_name("strasse").unwrap(), "Straße");
+        assert_eq!(manager.resolve_name("cafe\u{301}").unwrap(), "Caf\u{e9}");
+    }
+
+    #[test]
+    fn unique_substring_resolves() {
+        let manager = manager(&["alpha", "my-parser"]);
+        assert_eq!(manager.resolve_name("pars").unwrap(), "my-parser");
+    }
+
+    #[test]
+    fn ambiguous_name_lists_candidates() {
+        let manager = manager(&["website", "webapp", "alpha"]);
+        let error = manager.resolve_name("web").unwrap_err();
+        assert_eq!(
+            error.message,
+            "'web' matches several projects:\n  webapp\n  website"
+        );
+    }
+
+    #[test]
+    fn unknown_name_is_an_error() {
+        let manager = manager(&["alpha"]);
+        let error = manager.resolve_name("zeta").unwrap_err();
+        assert_eq!(error.kind, ProjectErrorTypes::NonExistingProject);
+    }
+
+    #[test]
+    fn search_ignores_case_of_names_and_tags() {
+        let web = Project::new(
+            "Site".into(),
+            datetime!(2025-06-01 9:00 UTC),
+            HashSet::from(["WebApp".to_owned()]),
+        );
+        let manager = ProjectManager::from_projects(vec![web]);
+        assert_eq!(manager.search("webapp", SortOrder::Name).len(), 1);
+        assert_eq!(manager.search("SITE", SortOrder::Name).len(), 1);
+        assert!(manager.search("cli", SortOrder::Name).is_empty());
+    }
+
+    #[test]
+    fn memory_root_archives_and_restores() {
+        let root = PathBuf::from("/projects");
+        let storage = Memory::new(&root);
+        for name in ["alpha", "beta"] {
+            let path = root.join(name);
+            storage.create_dir(&path).unwrap();
+            Project::new(name.into(), datetime!(2025-06-01 9:00 UTC), HashSet::new())
+                .save(&storage, path)
+                .unwrap();
+        }
+        storage.create_dir(&root.join("alpha/src")).unwrap();
+        storage
+            .write(&root.join("alpha/src/main.rs"), "fn main() {}")
+            .unwrap();
+        let mut manager = ProjectManager::load_with(root.clone(), Box::new(storage)).unwrap();
+        assert_eq!(
+            manager.project_at(&root.join("alpha/src")).as_deref(),
+            Some("alpha")
+        );
+        manager.archive("alpha", true).unwrap();
+        assert_eq!(manager.archived()[0].get_name(), "alpha");
+        assert!(manager.get_project("alpha").is_none());
+        manager.restore("alpha").unwrap();
+        assert!(manager.get_project("alpha").is_some());
+        assert_eq!(
+            manager
+                .storage
+                .read(&root.join("alpha/src/main.rs"))
+                .unwrap(),
+            "fn main() {}"
+        );
+    }
+
+    #[test]
+    fn locked_project_is_not_archived() {
+        let mut manager = manager(&["alpha"]);
+        manager.set_locked("alpha", true).unwrap();
+        let error = manager.archive("alpha", false).unwrap_err();
+        assert_eq!(error.kind, ProjectErrorTypes::Locked);
+        manager.unlock();
+        manager.archive("alpha", false).unwrap();
+        assert_eq!(manager.archived().len(), 1);
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        let storage = Memory::new(Path::new("/projects"));
+        assert!(ProjectManager::load_with("/elsewhere".into(), Box::new(storage)).is_err());
+    }
+
+    fn memory_manager(files: &[(&str, &str)]) -> ProjectManager {
+        let root = PathBuf::from("/projects");
+        let storage = Memory::new(&root);
+        for (name, tag) in [("alpha", "rust"), ("beta", "web")] {
+            let path = root.join(name);
+            storage.create_dir(&path).unwrap();
+            let tags = HashSet::from([tag.to_owned()]);
+            Project::new(name.into(), datetime!(2025-06-01 9:00 UTC), tags)
+                .save(&storage, path)
+                .unwrap();
+        }
+        for (file, data) in files {
+            storage.write(&root.join(file), data).unwrap();
+        }
+        ProjectManager::load_with(root, Box::new(storage)).unwrap()
+    }
+
+    #[test]
+    fn merge_moves_files_and_tags() {
+        let mut manager = memory_manager(&[
+            ("alpha/a.txt", "a"),
+            ("alpha/.gitignore", "target"),
+            ("beta/b.txt", "b"),
+            ("beta/.gitignore", "target"),
+        ]);
+        manager.merge("alpha", "beta").unwrap();
+        assert!(manager.get_project("alpha").is_none());
+        let beta = manager.get_project("beta").unwrap();
+        assert_eq!(
+            beta.get_tags(),
+            HashSet::from(["rust".to_owned(), "web".to_owned()])
+        );
+        let mut files = manager.storage.list(Path::new("/projects/beta")).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [".gitignore", PROJECT_FILE, "a.txt", "b.txt"]
+                .map(|f| PathBuf::from("/projects/beta").join(f))
+        );
+    }
+
+    #[test]
+    fn merge_refuses_clashing_files() {
+        let mut manager = memory_manager(&[("alpha/main.rs", "a"), ("beta/main.rs", "b")]);
+        assert!(manager.merge("alpha", "beta").is_err());
+        assert!(manager.get_project("alpha").is_some());
+        assert_eq!(
+            manager
+                .storage
+                .read(Path::new("/projects/beta/main.rs"))
+                .unwrap(),
+            "b"
+        );
+    }
+
+    #[test]
+    fn substitutions_parse() {
+        let rename = |s: &str, name: &str| s.parse::<Substitution>().unwrap().apply(name);
+        assert_eq!(rename("s/old/new/", "old-old").as_deref(), Some("new-old"));
+        assert_eq!(rename("s/old/new/g", "old-old").as_deref(), Some("new-new"));
+        assert_eq!(rename("s/-old//", "web-old").as_deref(), Some("web"));
+        assert_eq!(rename(r"s/a\/b/a-b/", "a/b").as_deref(), Some("a-b"));
+        assert_eq!(rename("s|x|/|", "x").as_deref(), Some("/"));
+        assert_eq!(
+            rename(r"s/(\w+)-(\w+)/\2-\1/", "web-app").as_deref(),
+            Some("app-web")
+        );
+        assert_eq!(rename("s/zzz/y/", "web"), None);
+    }
+
+    #[test]
+    fn malformed_substitutions_are_errors() {
+        for s in [
+            "", "s", "s/a/b", "s/a", "x/a/b/", "s/a/b/c/", "s/a/b/i", "s/(/b/",
+        ] {
+            assert!(s.parse::<Substitution>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn renames_to_one_name_are_rejected_before_renaming() {
+        let mut manager = manager(&["web-old", "web-new", "api"]);
+        let substitution: Substitution = "s/-(old|new)//".parse().unwrap();
+        let renames: Vec<(String, String)> = ["web-old", "web-new"]
+            .iter()
+            .map(|n| (n.to_string(), substitution.apply(n).unwrap()))
+            .collect();
+        let error = manager.check_renames(&renames).unwrap_err();
+        assert!(error
+            .message
+            .contains("more than one project would be named 'web'"));
+        let folded = [("api".to_owned(), "WEB-OLD".to_owned())];
+        assert!(manager.check_renames(&folded).is_err());
+        manager.set_locked("api", true).unwrap();
+        let locked = [("api".to_owned(), "rest".to_owned())];
+        assert!(manager.check_renames(&locked).is_err());
+        assert!(manager.get_project("web-old").is_some());
+        assert!(manager.get_project("web-new").is_some());
+    }
+}
@@ -0,0 +1,100 @@
+//! Projects living outside the root directory(added with `init`). Their paths
+//! are kept per root so profiles with different roots stay separate.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::paths;
+
+const REGISTRY_FILE: &str = "external.json";
+
+pub struct Registry {
+    path: Option<PathBuf>,
+    roots: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl Registry {
+    pub fn load() -> Self {
+        let path = paths::data(REGISTRY_FILE);
+        let roots = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Registry { path, roots }
+    }
+    /// Registry which isn't read from or saved to disk.
+    pub fn in_memory() -> Self {
+        Registry {
+            path: None,
+            roots: HashMap::new(),
+        }
+    }
+    /// Directories of external projects registered for `root`.
+    pub fn get(&self, root: &Path) -> &[PathBuf] {
+        self.roots
+            .get(root)
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
+    pub fn add(&mut self, root: &Path, dir: PathBuf) -> Result<(), String> {
+        let dirs = self.roots.entry(root.to_owned()).or_default();
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+        self.save()
+    }
+    pub fn remove(&mut self, root: &Path, dir: &Path) -> Result<(), String> {
+        if let Some(dirs) = self.roots.get_mut(root) {
+            dirs.retain(|d| d != dir);
+        }
+        self.save()
+    }
+    /// Unregisters external projects, of every root, whose directory doesn't
+    /// exist anymore. Returns their directories.
+    pub fn prune(&mut self) -> Result<Vec<PathBuf>, String> {
+        let mut removed = Vec::new();
+        for dirs in self.roots.values_mut() {
+            removed.extend(dirs.iter().filter(|d| !d.is_dir()).cloned());
+            dirs.retain(|d| d.is_dir());
+        }
+        self.roots.retain(|_, dirs| !dirs.is_empty());
+        if !removed.is_empty() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+    /// Registers the external projects of root `old` for root `new` instead.
+    /// Subprojects inside `old` are moved along.
+    pub fn move_root(&mut self, old: &Path, new: &Path) -> Result<(), String> {
+        let dirs = match self.roots.remove(old) {
+            Some(dirs) => dirs,
+            None => return Ok(()),
+        };
+        let moved = self.roots.entry(new.to_owned()).or_default();
+        for dir in dirs {
+            let dir = match dir.strip_prefix(old) {
+                Ok(relative) => new.join(relative),
+                Err(_) => dir,
+            };
+            if !moved.contains(&dir) {
+                moved.push(dir);
+            }
+        }
+        self.save()
+    }
+    fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.roots).unwrap())
+            .map_err(|e| e.to_string())
+    }
+}
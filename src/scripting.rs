@@ -0,0 +1,202 @@
+//! User scripts which customize behavior on certain events. Scripts are
+//! listed in the `scripts` config value and are only run when built with the
+//! `scripting` feature(using the rhai language).
+//!
+//! A script can define any of these functions:
+//! - `filter_project(project) -> bool`: called for every loaded project,
+//!   returning false hides it from every command.
+//! - `tags(project) -> array`: called when a project is created, returned
+//!   strings are added to its tags.
+//! - `veto_delete(project) -> bool | string`: called before a project is
+//!   moved to the trash, returning true or a reason keeps it.
+//!
+//! `project` is a map with `name`, `path`, `tags`(array of strings),
+//! `created` and `accessed`(ISO-8601 strings). All loaded projects are also
+//! available to scripts as the `projects` constant(an array of such maps).
+
+use std::{collections::HashSet, path::Path};
+
+use crate::project::Project;
+
+#[cfg(feature = "scripting")]
+pub struct Hooks {
+    engine: rhai::Engine,
+    scripts: Vec<rhai::AST>,
+    projects: rhai::Array,
+}
+
+#[cfg(feature = "scripting")]
+impl Hooks {
+    pub fn load(scripts: &[String]) -> Result<Self, String> {
+        let engine = rhai::Engine::new();
+        let scripts = scripts
+            .iter()
+            .map(|path| {
+                engine
+                    .compile_file(path.into())
+                    .map_err(|e| format!("Couldn't load script {}: {}", path, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Hooks {
+            engine,
+            scripts,
+            projects: rhai::Array::new(),
+        })
+    }
+    pub fn set_projects(&mut self, projects: &[(Project, std::path::PathBuf)]) {
+        self.projects = projects
+            .iter()
+            .map(|(project, path)| project_map(project, path))
+            .collect();
+    }
+    fn call(
+        &self,
+        name: &str,
+        project: &Project,
+        path: &Path,
+    ) -> Result<Vec<rhai::Dynamic>, String> {
+        let mut res = Vec::new();
+        for ast in &self.scripts {
+            if !ast.iter_functions().any(|f| f.name == name) {
+                continue;
+            }
+            let mut scope = rhai::Scope::new();
+            scope.push_constant("projects", self.projects.clone());
+            let value = self
+                .engine
+                .call_fn::<rhai::Dynamic>(&mut scope, ast, name, (project_map(project, path),))
+                .map_err(|e| format!("Script function {} failed: {}", name, e))?;
+            res.push(value);
+        }
+        Ok(res)
+    }
+    pub fn keep_project(&self, project: &Project, path: &Path) -> Result<bool, String> {
+        for keep in self.call("filter_project", project, path)? {
+            if !keep
+                .as_bool()
+                .map_err(|t| format!("filter_project returned {} instead of bool", t))?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    pub fn extra_tags(&self, project: &Project, path: &Path) -> Result<HashSet<String>, String> {
+        let mut tags = HashSet::new();
+        for res in self.call("tags", project, path)? {
+            let res = res
+                .into_array()
+                .map_err(|t| format!("tags returned {} instead of array", t))?;
+            tags.extend(res.into_iter().map(|tag| tag.to_string()));
+        }
+        Ok(tags)
+    }
+    /// Why a script refuses deleting `project`, if one does.
+    pub fn veto_delete(&self, project: &Project, path: &Path) -> Result<Option<String>, String> {
+        for res in self.call("veto_delete", project, path)? {
+            if res.is_string() {
+                return Ok(Some(res.to_string()));
+            }
+            if res
+                .as_bool()
+                .map_err(|t| format!("veto_delete returned {} instead of bool or string", t))?
+            {
+                return Ok(Some("vetoed by a script".into()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn project_map(project: &Project, path: &Path) -> rhai::Dynamic {
+    use time::format_description::well_known::Iso8601;
+
+    let mut map = rhai::Map::new();
+    map.insert("name".into(), project.get_name().clone().into());
+    map.insert("path".into(), path.to_string_lossy().to_string().into());
+    map.insert(
+        "tags".into(),
+        project
+            .get_tags()
+            .into_iter()
+            .map(rhai::Dynamic::from)
+            .collect::<rhai::Array>()
+            .into(),
+    );
+    for (key, time) in [
+        ("created", project.get_created()),
+        ("accessed", project.get_accessed()),
+    ] {
+        map.insert(
+            key.into(),
+            time.format(&Iso8601::DEFAULT).unwrap_or_default().into(),
+        );
+    }
+    map.into()
+}
+
+/// Stand-in used when built without the `scripting` feature: configuring
+/// scripts is an error and every hook is a no-op.
+#[cfg(not(feature = "scripting"))]
+pub struct Hooks;
+
+#[cfg(not(feature = "scripting"))]
+impl Hooks {
+    pub fn load(scripts: &[String]) -> Result<Self, String> {
+        if !scripts.is_empty() {
+            return Err(
+                "scripts are configured but this build doesn't have the scripting feature".into(),
+            );
+        }
+        Ok(Hooks)
+    }
+    pub fn set_projects(&mut self, _: &[(Project, std::path::PathBuf)]) {}
+    pub fn keep_project(&self, _: &Project, _: &Path) -> Result<bool, String> {
+        Ok(true)
+    }
+    pub fn extra_tags(&self, _: &Project, _: &Path) -> Result<HashSet<String>, String> {
+        Ok(HashSet::new())
+    }
+    pub fn veto_delete(&self, _: &Project, _: &Path) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn hooks(script: &str) -> (Hooks, tempfile::TempPath) {
+        let file = tempfile::Builder::new().suffix(".rhai").tempfile().unwrap();
+        std::fs::write(file.path(), script).unwrap();
+        let path = file.into_temp_path();
+        let hooks = Hooks::load(&[path.to_string_lossy().to_string()]).unwrap();
+        (hooks, path)
+    }
+
+    fn project(name: &str) -> Project {
+        Project::new(name.into(), datetime!(2025-06-01 9:00 UTC), HashSet::new())
+    }
+
+    #[test]
+    fn veto_delete_keeps_projects() {
+        let (hooks, _file) = hooks(
+            r#"fn veto_delete(project) {
+                if project.name == "prod" { "it's live" } else { project.name == "keep" }
+            }"#,
+        );
+        let path = Path::new("/projects");
+        assert_eq!(
+            hooks
+                .veto_delete(&project("prod"), path)
+                .unwrap()
+                .as_deref(),
+            Some("it's live")
+        );
+        assert!(hooks.veto_delete(&project("keep"), path).unwrap().is_some());
+        assert_eq!(hooks.veto_delete(&project("old"), path).unwrap(), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! A JSON-RPC 2.0 server over stdin/stdout for editor integrations. Every
+//! line of input is one request and every response is written as one line.
+//! Projects are loaded once so requests don't re-scan the root.
+//!
+//! Methods:
+//! - `list` `{"order": "accessed" | "created" | "name"}`: all projects
+//! - `search` `{"query": string}`: projects whose name or tags contain query
+//! - `open` `{"name": string}`: marks project as accessed and returns it,
+//!   the editor is expected to open the returned path itself
+//! - `modify` `{"name": string, "tags": [string]}`: replaces tags of project,
+//!   returning it with `diff`, the `added` and `removed` tags
+//!
+//! Accessed times set by `open` are saved in batches, see
+//! [`ProjectManager::batch_access`].
+//!
+//! Projects are returned as objects with `name`, `path`, `tags`, `created`
+//! and `accessed`.
+
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{ProjectError, ProjectErrorTypes},
+    history,
+    project::{Project, ProjectManager, SortOrder},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+struct RpcError(i64, String, Value);
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError(SERVER_ERROR, message, Value::Null)
+    }
+}
+
+impl From<ProjectError> for RpcError {
+    fn from(e: ProjectError) -> Self {
+        let data = match e.kind {
+            ProjectErrorTypes::NonExistingProject => json!({"suggestions": e.suggestions}),
+            _ => Value::Null,
+        };
+        RpcError(SERVER_ERROR, e.message, data)
+    }
+}
+
+fn project_json(manager: &ProjectManager, project: &Project) -> Value {
+    let mut value = serde_json::to_value(project).unwrap();
+    value["path"] = json!(manager.get_path(project.get_name()));
+    value
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params[name].as_str().ok_or(RpcError(
+        INVALID_PARAMS,
+        format!("missing string parameter '{}'", name),
+        Value::Null,
+    ))
+}
+
+fn call(manager: &mut ProjectManager, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "list" => {
+            let order = match params["order"].as_str() {
+                Some("created") => SortOrder::Creation,
+                Some("name") => SortOrder::Name,
+                _ => SortOrder::AccessTime,
+            };
+            Ok(manager
+                .iter_projects(order)
+                .map(|p| project_json(manager, p))
+                .collect())
+        }
+        "search" => {
+            let query = str_param(params, "query")?;
+            Ok(manager
+                .search(query, SortOrder::AccessTime)
+                .iter()
+                .map(|p| project_json(manager, p))
+                .collect())
+        }
+        "open" => {
+            let name = str_param(params, "name")?;
+            manager.touch(name)?;
+            let project = manager.get_mut_project(name)?.clone();
+            Ok(project_json(manager, &project))
+        }
+        "modify" => {
+            let name = str_param(params, "name")?;
+            let tags: HashSet<String> =
+                serde_json::from_value(params["tags"].clone()).map_err(|e| {
+                    RpcError(
+                        INVALID_PARAMS,
+                        format!("invalid 'tags': {}", e),
+                        Value::Null,
+                    )
+                })?;
+            for tag in &tags {
+                manager.insert_tag(tag.to_owned());
+            }
+            let old = manager.get_mut_project(name)?.get_tags();
+            manager.modify(name, tags)?;
+            let project = manager.get_mut_project(name)?.clone();
+            let diff = history::record_tags("modify", name, &old, &project.get_tags());
+            let mut value = project_json(manager, &project);
+            value["diff"] = json!(diff);
+            Ok(value)
+        }
+        _ => Err(RpcError(
+            METHOD_NOT_FOUND,
+            format!("unknown method '{}'", method),
+            Value::Null,
+        )),
+    }
+}
+
+fn respond(manager: &mut ProjectManager, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(json!({"jsonrpc": "2.0", "id": null,
+                "error": {"code": PARSE_ERROR, "message": e.to_string()}}))
+        }
+    };
+    // requests without an id are notifications which don't get a response
+    let id = request.get("id")?.clone();
+    let res = match request["method"].as_str() {
+        Some(method) => call(manager, method, &request["params"]),
+        None => Err(RpcError(
+            INVALID_REQUEST,
+            "missing method".into(),
+            Value::Null,
+        )),
+    };
+    Some(match res {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(RpcError(code, message, Value::Null)) => {
+            json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+        }
+        Err(RpcError(code, message, data)) => json!({"jsonrpc": "2.0", "id": id,
+            "error": {"code": code, "message": message, "data": data}}),
+    })
+}
+
+/// Accessed times of opened projects are saved at most this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Serves requests from stdin until it's closed.
+pub fn serve_stdio(mut manager: ProjectManager) -> Result<(), String> {
+    let stdout = io::stdout();
+    manager.batch_access();
+    let mut flushed = Instant::now();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&mut manager, &line) {
+            let mut out = stdout.lock();
+            writeln!(out, "{}", response).map_err(|e| e.to_string())?;
+            out.flush().map_err(|e| e.to_string())?;
+        }
+        if flushed.elapsed() >= FLUSH_INTERVAL {
+            manager.flush()?;
+            flushed = Instant::now();
+        }
+    }
+    Ok(manager.flush()?)
+}
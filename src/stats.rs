@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{history::Entry, project::Project};
+
+const LEVELS: [&str; 5] = ["· ", "░░", "▒▒", "▓▓", "██"];
+const DAYS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+// dim, normal, bold and bold cyan, from least to most used
+const WEIGHTS: [&str; 4] = ["\x1b[2m", "", "\x1b[1m", "\x1b[1;36m"];
+
+/// Counts how many projects were accessed on each day(in the timezone of `offset`).
+pub fn activity<'a>(
+    projects: impl IntoIterator<Item = &'a Project>,
+    offset: UtcOffset,
+) -> HashMap<Date, usize> {
+    let mut days = HashMap::new();
+    for project in projects {
+        *days
+            .entry(project.get_accessed().to_offset(offset).date())
+            .or_insert(0) += 1;
+    }
+    days
+}
+
+/// Renders a GitHub-style calendar of the last `weeks` weeks(ending today)
+/// with one row per weekday and one column per week.
+pub fn heatmap(activity: &HashMap<Date, usize>, weeks: u16, offset: UtcOffset) -> String {
+    let today = OffsetDateTime::now_utc().to_offset(offset).date();
+    let monday = today - Duration::days(today.weekday().number_days_from_monday() as i64);
+    let start = monday - Duration::weeks(weeks as i64 - 1);
+    let max = activity
+        .iter()
+        .filter(|(date, _)| **date >= start)
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0);
+
+    let mut res = String::from("    ");
+    let mut last_month = None;
+    let mut skip = 0;
+    for week in 0..weeks {
+        let date = start + Duration::weeks(week as i64);
+        if skip > 0 {
+            skip -= 1;
+        } else if last_month != Some(date.month()) {
+            last_month = Some(date.month());
+            res += &date.month().to_string()[..3];
+            // month names take 3 columns and each week takes 2
+            skip = 1;
+            res += " ";
+            continue;
+        } else {
+            res += "  ";
+        }
+    }
+    res += "\n";
+
+    for (day, label) in DAYS.iter().enumerate() {
+        res += &format!("{:<4}", label);
+        for week in 0..weeks {
+            let date = start + Duration::days(week as i64 * 7 + day as i64);
+            if date > today {
+                break;
+            }
+            let count = activity.get(&date).copied().unwrap_or(0);
+            let level = match count {
+                0 => 0,
+                _ => (count * (LEVELS.len() - 1)).div_ceil(max),
+            };
+            res += LEVELS[level];
+        }
+        res += "\n";
+    }
+    res += &format!("    less {} more\n", LEVELS.concat());
+    res
+}
+
+/// Renders tags alphabetically like "rust(5)", wrapped to `width` columns.
+/// With `color`, more used tags are brighter and bolder.
+pub fn tag_cloud(tags: &HashMap<String, usize>, width: usize, color: bool) -> String {
+    let max = tags.values().copied().max().unwrap_or(1);
+    let mut tags: Vec<(&String, &usize)> = tags.iter().collect();
+    tags.sort();
+    let mut res = String::new();
+    let mut line = 0;
+    for (tag, count) in tags {
+        let text = format!("{}({})", tag, count);
+        let text_width = text.width();
+        if line > 0 && line + 2 + text_width > width {
+            res += "\n";
+            line = 0;
+        } else if line > 0 {
+            res += "  ";
+            line += 2;
+        }
+        line += text_width;
+        if color {
+            let weight = (count * WEIGHTS.len()).div_ceil(max) - 1;
+            res += &format!("{}{}\x1b[0m", WEIGHTS[weight], text);
+        } else {
+            res += &text;
+        }
+    }
+    if !res.is_empty() {
+        res += "\n";
+    }
+    res
+}
+
+/// Projects created, renamed, modified(with what changed) and otherwise
+/// accessed since `since` as a Markdown list, from history `entries`(oldest
+/// first) and access times of `projects`. Names follow later renames.
+pub fn summary<'a>(
+    projects: impl IntoIterator<Item = &'a Project>,
+    entries: &[Entry],
+    since: OffsetDateTime,
+) -> String {
+    let mut created = BTreeSet::new();
+    let mut renamed: Vec<(String, String)> = Vec::new();
+    let mut modified: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.time >= since) {
+        match entry.command.as_str() {
+            "create" | "init" => {
+                created.insert(entry.project.clone());
+            }
+            "rename" => {
+                if created.remove(&entry.old) {
+                    created.insert(entry.new.clone());
+                } else if let Some(rename) = renamed.iter_mut().find(|(_, new)| *new == entry.old) {
+                    rename.1 = entry.new.clone();
+                } else {
+                    renamed.push((entry.old.clone(), entry.new.clone()));
+                }
+                if let Some(changes) = modified.remove(&entry.old) {
+                    modified.insert(entry.new.clone(), changes);
+                }
+            }
+            // access times already cover it
+            "touch" => {}
+            "delete" => {
+                created.remove(&entry.project);
+                modified
+                    .entry(entry.project.clone())
+                    .or_default()
+                    .insert("delete");
+            }
+            command => {
+                modified
+                    .entry(entry.project.clone())
+                    .or_default()
+                    .insert(command);
+            }
+        }
+    }
+    modified.retain(|name, _| !created.contains(name));
+    // renamed back and forth
+    renamed.retain(|(old, new)| old != new);
+    let mut touched: Vec<&str> = projects
+        .into_iter()
+        .filter(|p| p.get_accessed() >= since)
+        .map(|p| p.get_name().as_str())
+        .filter(|name| {
+            !created.contains(*name)
+                && !modified.contains_key(*name)
+                && !renamed.iter().any(|(_, new)| new == name)
+        })
+        .collect();
+    touched.sort();
+
+    let mut res = String::new();
+    let mut section = |title: &str, items: Vec<String>| {
+        if !items.is_empty() {
+            res += &format!("{}:\n", title);
+            for item in items {
+                res += &format!("- {}\n", item);
+            }
+        }
+    };
+    section("Created", created.into_iter().collect());
+    section(
+        "Renamed",
+        renamed
+            .into_iter()
+            .map(|(old, new)| format!("{} -> {}", old, new))
+            .collect(),
+    );
+    section(
+        "Modified",
+        modified
+            .into_iter()
+            .map(|(name, changes)| {
+                format!(
+                    "{} ({})",
+                    name,
+                    changes.into_iter().collect::<Vec<_>>().join(", ")
+                )
+            })
+            .collect(),
+    );
+    section("Touched", touched.into_iter().map(str::to_owned).collect());
+    res
+}
@@ -0,0 +1,365 @@
+//! Filesystem operations used by `ProjectManager`, so projects can be kept
+//! somewhere other than the local disk(e.g. in memory for tests).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{archive, registry::Registry};
+
+/// Shared between threads by bulk saves, see `ProjectManager::save_all`.
+pub trait Storage: Send + Sync {
+    fn is_dir(&self, path: &Path) -> bool;
+    fn is_file(&self, path: &Path) -> bool;
+    /// Direct subdirectories of `path`.
+    fn list_dirs(&self, path: &Path) -> Result<Vec<PathBuf>, String>;
+    /// Files and directories directly inside `path`.
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, String>;
+    fn read(&self, path: &Path) -> Result<String, String>;
+    fn write(&self, path: &Path, data: &str) -> Result<(), String>;
+    fn append(&self, path: &Path, data: &str) -> Result<(), String>;
+    fn create_dir(&self, path: &Path) -> Result<(), String>;
+    /// Creates `path` and its missing parents.
+    fn create_dir_all(&self, path: &Path) -> Result<(), String> {
+        let missing: Vec<&Path> = path
+            .ancestors()
+            .take_while(|dir| !self.is_dir(dir))
+            .collect();
+        for dir in missing.into_iter().rev() {
+            self.create_dir(dir)?;
+        }
+        Ok(())
+    }
+    /// Moves a file, or a directory with everything inside it.
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), String>;
+    /// Copies a directory with everything inside it, `to` must not exist.
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<(), String>;
+    /// Deletes a directory with everything inside it.
+    fn remove_dir(&self, path: &Path) -> Result<(), String>;
+    fn remove_file(&self, path: &Path) -> Result<(), String>;
+    /// Whether the current user can write to `path`(create files in it, for
+    /// directories). Missing paths count as writable.
+    fn is_writable(&self, path: &Path) -> bool;
+    /// Packs everything inside `dir` into the file `file`, for
+    /// `archive --compress`.
+    fn compress(&self, dir: &Path, file: &Path) -> Result<(), String>;
+    /// Unpacks `file` written by `compress` into `dir`.
+    fn extract(&self, file: &Path, dir: &Path) -> Result<(), String>;
+    /// Registry of the projects outside root kept alongside this storage.
+    /// Only the local disk has one, other storages start empty and don't
+    /// save it.
+    fn registry(&self) -> Registry {
+        Registry::in_memory()
+    }
+}
+
+/// The local filesystem.
+pub struct Disk;
+
+impl Storage for Disk {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+    fn list_dirs(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(path).map_err(|e| format!("Couldn't read {:?}: {}", path, e))?;
+        Ok(entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect())
+    }
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(path).map_err(|e| format!("Couldn't read {:?}: {}", path, e))?;
+        Ok(entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+    }
+    fn read(&self, path: &Path) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|e| format!("Couldn't read {:?}: {}", path, e))
+    }
+    fn write(&self, path: &Path, data: &str) -> Result<(), String> {
+        fs::write(path, data).map_err(|e| e.to_string())
+    }
+    fn append(&self, path: &Path, data: &str) -> Result<(), String> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("Couldn't open {:?}: {}", path, e))?;
+        file.write_all(data.as_bytes()).map_err(|e| e.to_string())
+    }
+    fn create_dir(&self, path: &Path) -> Result<(), String> {
+        fs::create_dir(path).map_err(|e| format!("Couldn't create {:?}: {}", path, e))
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), String> {
+        fs::rename(from, to).map_err(|e| format!("Couldn't rename {:?} to {:?}.\n{}", from, to, e))
+    }
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("Couldn't copy {:?} to {:?}: {}", from, to, e);
+        fs::create_dir(to).map_err(err)?;
+        for entry in fs::read_dir(from).map_err(err)? {
+            let entry = entry.map_err(err)?;
+            let (src, dst) = (entry.path(), to.join(entry.file_name()));
+            let kind = entry.file_type().map_err(err)?;
+            if kind.is_dir() {
+                self.copy_dir(&src, &dst)?;
+            } else if kind.is_symlink() {
+                copy_link(&src, &dst).map_err(err)?;
+            } else {
+                fs::copy(&src, &dst).map_err(err)?;
+            }
+        }
+        Ok(())
+    }
+    fn remove_dir(&self, path: &Path) -> Result<(), String> {
+        fs::remove_dir_all(path).map_err(|e| format!("Couldn't remove {:?}: {}", path, e))
+    }
+    fn remove_file(&self, path: &Path) -> Result<(), String> {
+        fs::remove_file(path).map_err(|e| format!("Couldn't remove {:?}: {}", path, e))
+    }
+    #[cfg(unix)]
+    fn is_writable(&self, path: &Path) -> bool {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+            return true;
+        };
+        // unlike the mode bits, access() accounts for the owner, groups and
+        // read-only mounts
+        !path.exists() || unsafe { libc::access(c_path.as_ptr(), libc::W_OK) } == 0
+    }
+    #[cfg(not(unix))]
+    fn is_writable(&self, path: &Path) -> bool {
+        fs::metadata(path)
+            .map(|m| !m.permissions().readonly())
+            .unwrap_or(true)
+    }
+    fn compress(&self, dir: &Path, file: &Path) -> Result<(), String> {
+        archive::compress(dir, file)
+    }
+    fn extract(&self, file: &Path, dir: &Path) -> Result<(), String> {
+        archive::extract(file, dir)
+    }
+    fn registry(&self) -> Registry {
+        Registry::load()
+    }
+}
+
+/// Recreates symlink `from` at `to` instead of copying what it points to.
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Directories and files kept in memory, nothing touches the disk.
+#[derive(Default)]
+pub struct Memory {
+    dirs: Mutex<BTreeSet<PathBuf>>,
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl Memory {
+    /// Storage containing only the(empty) directory `root`.
+    pub fn new(root: &Path) -> Self {
+        let memory = Memory::default();
+        memory.dirs.lock().unwrap().insert(root.to_owned());
+        memory
+    }
+    fn check_parent(&self, path: &Path) -> Result<(), String> {
+        match path.parent() {
+            Some(parent) if self.is_dir(parent) => Ok(()),
+            _ => Err(format!("Parent of {:?} doesn't exist", path)),
+        }
+    }
+}
+
+impl Storage for Memory {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.lock().unwrap().contains(path)
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(path)
+    }
+    fn list_dirs(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        if !self.is_dir(path) {
+            return Err(format!("Couldn't read {:?}: not a directory", path));
+        }
+        Ok(self
+            .dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|d| d.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        let mut entries = self.list_dirs(path)?;
+        entries.extend(
+            self.files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|f| f.parent() == Some(path))
+                .cloned(),
+        );
+        Ok(entries)
+    }
+    fn read(&self, path: &Path) -> Result<String, String> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or(format!("Couldn't read {:?}: no such file", path))
+    }
+    fn write(&self, path: &Path, data: &str) -> Result<(), String> {
+        self.check_parent(path)?;
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), data.to_owned());
+        Ok(())
+    }
+    fn append(&self, path: &Path, data: &str) -> Result<(), String> {
+        self.check_parent(path)?;
+        self.files
+            .lock()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_default()
+            .push_str(data);
+        Ok(())
+    }
+    fn create_dir(&self, path: &Path) -> Result<(), String> {
+        self.check_parent(path)?;
+        if !self.dirs.lock().unwrap().insert(path.to_owned()) {
+            return Err(format!("Couldn't create {:?}: already exists", path));
+        }
+        Ok(())
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), String> {
+        if self.is_file(from) && !self.is_dir(to) {
+            self.check_parent(to)?;
+            let mut files = self.files.lock().unwrap();
+            let data = files.remove(from).unwrap();
+            files.insert(to.to_owned(), data);
+            return Ok(());
+        }
+        if !self.is_dir(from) || self.is_dir(to) {
+            return Err(format!("Couldn't rename {:?} to {:?}", from, to));
+        }
+        let moved = |p: &PathBuf| to.join(p.strip_prefix(from).unwrap());
+        let mut dirs = self.dirs.lock().unwrap();
+        let inside: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|d| d.starts_with(from))
+            .cloned()
+            .collect();
+        for dir in inside {
+            dirs.remove(&dir);
+            dirs.insert(moved(&dir));
+        }
+        let mut files = self.files.lock().unwrap();
+        let inside: Vec<PathBuf> = files
+            .keys()
+            .filter(|f| f.starts_with(from))
+            .cloned()
+            .collect();
+        for file in inside {
+            let data = files.remove(&file).unwrap();
+            files.insert(moved(&file), data);
+        }
+        Ok(())
+    }
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<(), String> {
+        self.check_parent(to)?;
+        if !self.is_dir(from) || self.is_dir(to) {
+            return Err(format!("Couldn't copy {:?} to {:?}", from, to));
+        }
+        let copied = |p: &PathBuf| to.join(p.strip_prefix(from).unwrap());
+        let mut dirs = self.dirs.lock().unwrap();
+        let inside: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|d| d.starts_with(from))
+            .map(copied)
+            .collect();
+        dirs.extend(inside);
+        let mut files = self.files.lock().unwrap();
+        let inside: Vec<(PathBuf, String)> = files
+            .iter()
+            .filter(|(f, _)| f.starts_with(from))
+            .map(|(f, data)| (copied(f), data.clone()))
+            .collect();
+        files.extend(inside);
+        Ok(())
+    }
+    fn remove_dir(&self, path: &Path) -> Result<(), String> {
+        if !self.dirs.lock().unwrap().remove(path) {
+            return Err(format!("Couldn't remove {:?}: no such directory", path));
+        }
+        self.dirs.lock().unwrap().retain(|d| !d.starts_with(path));
+        self.files
+            .lock()
+            .unwrap()
+            .retain(|f, _| !f.starts_with(path));
+        Ok(())
+    }
+    fn remove_file(&self, path: &Path) -> Result<(), String> {
+        match self.files.lock().unwrap().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(format!("Couldn't remove {:?}: no such file", path)),
+        }
+    }
+    fn is_writable(&self, _path: &Path) -> bool {
+        true
+    }
+    /// Keeps the directories and files inside `dir` as JSON, uncompressed.
+    fn compress(&self, dir: &Path, file: &Path) -> Result<(), String> {
+        if !self.is_dir(dir) {
+            return Err(format!("Couldn't compress {:?}: no such directory", dir));
+        }
+        let relative = |p: &PathBuf| p.strip_prefix(dir).unwrap().to_owned();
+        let dirs: Vec<PathBuf> = self
+            .dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|d| d.starts_with(dir) && *d != dir)
+            .map(relative)
+            .collect();
+        let files: BTreeMap<PathBuf, String> = self
+            .files
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(f, _)| f.starts_with(dir))
+            .map(|(f, data)| (relative(f), data.clone()))
+            .collect();
+        let data = serde_json::to_string(&(dirs, files)).map_err(|e| e.to_string())?;
+        self.write(file, &data)
+    }
+    fn extract(&self, file: &Path, dir: &Path) -> Result<(), String> {
+        let (dirs, files): (Vec<PathBuf>, BTreeMap<PathBuf, String>) =
+            serde_json::from_str(&self.read(file)?)
+                .map_err(|e| format!("Couldn't extract {:?}: {}", file, e))?;
+        self.check_parent(dir)?;
+        let mut all_dirs = self.dirs.lock().unwrap();
+        all_dirs.insert(dir.to_owned());
+        all_dirs.extend(dirs.iter().map(|d| dir.join(d)));
+        self.files
+            .lock()
+            .unwrap()
+            .extend(files.into_iter().map(|(f, data)| (dir.join(f), data)));
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Tasks defined by the build tools of a project(justfile recipes, Makefile
+//! targets and package.json scripts), used by `run`.
+
+use std::{fmt::Display, fs, path::Path};
+
+use regex::Regex;
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Runner {
+    Just,
+    Make,
+    Npm,
+}
+
+impl Display for Runner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Runner::Just => "just",
+            Runner::Make => "make",
+            Runner::Npm => "npm",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+pub struct Task {
+    pub name: String,
+    pub runner: Runner,
+}
+
+impl Task {
+    /// Command running the task from the project directory.
+    pub fn command(&self) -> String {
+        match self.runner {
+            Runner::Npm => format!("npm run {}", self.name),
+            runner => format!("{} {}", runner, self.name),
+        }
+    }
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.runner)
+    }
+}
+
+/// Tasks of the project in `dir`, grouped by runner in the order just, make
+/// and npm. Files which can't be read are skipped.
+pub fn discover(dir: &Path) -> Vec<Task> {
+    let read = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|n| fs::read_to_string(dir.join(n)).ok())
+    };
+    let mut tasks = Vec::new();
+    if let Some(data) = read(&["justfile", "Justfile", ".justfile"]) {
+        tasks.extend(just_recipes(&data).into_iter().map(|name| Task {
+            name,
+            runner: Runner::Just,
+        }));
+    }
+    if let Some(data) = read(&["GNUmakefile", "makefile", "Makefile"]) {
+        tasks.extend(make_targets(&data).into_iter().map(|name| Task {
+            name,
+            runner: Runner::Make,
+        }));
+    }
+    if let Some(data) = read(&["package.json"]) {
+        tasks.extend(npm_scripts(&data).into_iter().map(|name| Task {
+            name,
+            runner: Runner::Npm,
+        }));
+    }
+    tasks
+}
+
+/// Public recipes, skipping private ones(starting with `_`) and
+/// assignments(`name := value`).
+fn just_recipes(data: &str) -> Vec<String> {
+    let recipe = Regex::new(r"^@?([A-Za-z_][A-Za-z0-9_-]*)(\s[^:]*)?:([^=]|$)").unwrap();
+    data.lines()
+        .filter_map(|line| recipe.captures(line))
+        .map(|c| c[1].to_owned())
+        .filter(|name| !name.starts_with('_'))
+        .collect()
+}
+
+/// Explicit targets, skipping special(`.PHONY`), pattern(`%.o`) and
+/// variable(`CC := gcc`) lines.
+fn make_targets(data: &str) -> Vec<String> {
+    let rule = Regex::new(r"^([^\s:#=%.][^:#=%]*):([^=]|$)").unwrap();
+    let mut res: Vec<String> = Vec::new();
+    for captures in data.lines().filter_map(|line| rule.captures(line)) {
+        for target in captures[1].split_whitespace() {
+            if !target.contains('$') && !res.iter().any(|t| t == target) {
+                res.push(target.to_owned());
+            }
+        }
+    }
+    res
+}
+
+fn npm_scripts(data: &str) -> Vec<String> {
+    serde_json::from_str::<Value>(data)
+        .ok()
+        .and_then(|v| {
+            v.get("scripts")?
+                .as_object()
+                .map(|s| s.keys().cloned().collect())
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,174 @@
+//! Fixtures for exercising `ProjectManager` against a real directory, for
+//! integration tests of this crate and of crates embedding it. Enabled with
+//! the `testing` feature. Nothing outside the temporary root is read or
+//! written, the registry of external projects is kept in memory.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    project::{Project, ProjectManager},
+    storage::{Disk, Storage},
+};
+
+const TAGS: [&str; 3] = ["rust", "python", "web"];
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// An isolated root directory in the system temp directory, removed when
+/// dropped.
+pub struct TempRoot {
+    path: PathBuf,
+}
+
+impl TempRoot {
+    /// Creates an empty root.
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "cli-project-manager-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        TempRoot { path }
+    }
+    /// Creates a root with projects `proj0` to `proj{count - 1}`. Each
+    /// project is created a day after the previous one and gets one of a few
+    /// fixed tags in turn.
+    pub fn with_projects(count: usize) -> Self {
+        let root = TempRoot::new();
+        let start = OffsetDateTime::now_utc() - Duration::days(count as i64);
+        for i in 0..count {
+            root.add_project_at(
+                &format!("proj{}", i),
+                &[TAGS[i % TAGS.len()]],
+                start + Duration::days(i as i64),
+            );
+        }
+        root
+    }
+    /// Creates a project in this root created right now.
+    pub fn add_project(&self, name: &str, tags: &[&str]) -> PathBuf {
+        self.add_project_at(name, tags, OffsetDateTime::now_utc())
+    }
+    /// Creates a project in this root with `created` as its creation and
+    /// access time.
+    pub fn add_project_at(&self, name: &str, tags: &[&str], created: OffsetDateTime) -> PathBuf {
+        let tags: HashSet<String> = tags.iter().map(|t| t.to_string()).collect();
+        self.manager()
+            .create(Project::new(name.to_owned(), created, tags))
+            .unwrap();
+        self.path.join(name)
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Loads a fresh manager over this root.
+    pub fn manager(&self) -> ProjectManager {
+        ProjectManager::load_with(self.path.clone(), Box::new(Isolated)).unwrap()
+    }
+}
+
+impl Default for TempRoot {
+    fn default() -> Self {
+        TempRoot::new()
+    }
+}
+
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// The local disk, without the user's registry of external projects.
+struct Isolated;
+
+impl Storage for Isolated {
+    fn is_dir(&self, path: &Path) -> bool {
+        Disk.is_dir(path)
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        Disk.is_file(path)
+    }
+    fn list_dirs(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        Disk.list_dirs(path)
+    }
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, String> {
+        Disk.list(path)
+    }
+    fn read(&self, path: &Path) -> Result<String, String> {
+        Disk.read(path)
+    }
+    fn write(&self, path: &Path, data: &str) -> Result<(), String> {
+        Disk.write(path, data)
+    }
+    fn append(&self, path: &Path, data: &str) -> Result<(), String> {
+        Disk.append(path, data)
+    }
+    fn create_dir(&self, path: &Path) -> Result<(), String> {
+        Disk.create_dir(path)
+    }
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), String> {
+        Disk.rename(from, to)
+    }
+    fn copy_dir(&self, from: &Path, to: &Path) -> Result<(), String> {
+        Disk.copy_dir(from, to)
+    }
+    fn remove_dir(&self, path: &Path) -> Result<(), String> {
+        Disk.remove_dir(path)
+    }
+    fn remove_file(&self, path: &Path) -> Result<(), String> {
+        Disk.remove_file(path)
+    }
+    fn is_writable(&self, path: &Path) -> bool {
+        Disk.is_writable(path)
+    }
+    fn compress(&self, dir: &Path, file: &Path) -> Result<(), String> {
+        Disk.compress(dir, file)
+    }
+    fn extract(&self, file: &Path, dir: &Path) -> Result<(), String> {
+        Disk.extract(file, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::SortOrder;
+
+    use super::*;
+
+    #[test]
+    fn projects_are_created_in_the_temp_root() {
+        let root = TempRoot::with_projects(4);
+        let manager = root.manager();
+        let names: Vec<&str> = manager
+            .iter_projects(SortOrder::Creation)
+            .map(|p| p.get_name().as_str())
+            .collect();
+        assert_eq!(names, ["proj3", "proj2", "proj1", "proj0"]);
+        assert!(manager
+            .get_project("proj3")
+            .unwrap()
+            .get_tags()
+            .contains("rust"));
+        assert!(root.path().join("proj2").is_dir());
+    }
+
+    #[test]
+    fn temp_root_is_removed_on_drop() {
+        let root = TempRoot::new();
+        let path = root.add_project("alpha", &["web"]);
+        drop(root);
+        assert!(!path.exists());
+    }
+}
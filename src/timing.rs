@@ -0,0 +1,42 @@
+//! Durations of the phases of a run, printed to stderr by `--timing`.
+
+use std::time::{Duration, Instant};
+
+use crate::project::ScanStats;
+
+#[derive(Default)]
+pub struct Timings {
+    phases: Vec<(String, Duration)>,
+    scan: Option<ScanStats>,
+}
+
+impl Timings {
+    /// Runs `f`, recording how long it took as `phase`.
+    pub fn time<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.phases.push((phase.to_owned(), start.elapsed()));
+        res
+    }
+    /// Breakdown of the scan, shown next to the phase named `scan`.
+    pub fn set_scan(&mut self, stats: ScanStats) {
+        self.scan = Some(stats);
+    }
+    pub fn report(&self) -> String {
+        let width = self.phases.iter().map(|(p, _)| p.len()).max().unwrap_or(0);
+        let mut res = String::new();
+        for (phase, duration) in &self.phases {
+            res += &format!("{:width$}  {:>10.2?}", phase, duration);
+            if let (Some(stats), "scan") = (self.scan, phase.as_str()) {
+                res += &format!(
+                    "  ({} directories, {:.2?} reading, {:.2?} parsing)",
+                    stats.dirs, stats.read, stats.parse
+                );
+            }
+            res += "\n";
+        }
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        res += &format!("{:width$}  {:>10.2?}\n", "total", total);
+        res
+    }
+}
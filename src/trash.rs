@@ -0,0 +1,98 @@
+//! Deleted projects are moved into a trash directory in root instead of being
+//! removed right away. A journal inside it remembers where each one came from
+//! so it can be restored until it's purged.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+use crate::storage::Storage;
+
+/// Directory in root which deleted projects are moved into.
+pub const TRASH_DIR: &str = ".trash";
+const JOURNAL_FILE: &str = "journal.json";
+
+/// A deleted project, kept in directory `id` of the trash.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub id: String,
+    pub name: String,
+    /// Directory the project was deleted from
+    pub path: PathBuf,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted: OffsetDateTime,
+}
+
+impl Entry {
+    pub fn new(name: &str, path: PathBuf) -> Self {
+        let deleted = OffsetDateTime::now_utc();
+        Entry {
+            id: format!("{}-{}", name, deleted.unix_timestamp_nanos()),
+            name: name.to_owned(),
+            path,
+            deleted,
+        }
+    }
+    /// Whether the entry is older than `keep` and should be purged.
+    pub fn expired(&self, keep: Duration) -> bool {
+        OffsetDateTime::now_utc() - self.deleted > keep
+    }
+}
+
+/// Entries of the trash in `root`, oldest first.
+pub fn entries(storage: &dyn Storage, root: &Path) -> Result<Vec<Entry>, String> {
+    let path = root.join(TRASH_DIR).join(JOURNAL_FILE);
+    if !storage.is_file(&path) {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&storage.read(&path)?)
+        .map_err(|e| format!("Broken trash journal {:?}: {}", path, e))
+}
+
+/// Removes entries whose directory in the trash of `root` is gone from the
+/// journal. Returns the removed entries.
+pub fn prune(storage: &dyn Storage, root: &Path) -> Result<Vec<Entry>, String> {
+    let (kept, removed): (Vec<Entry>, Vec<Entry>) = entries(storage, root)?
+        .into_iter()
+        .partition(|e| storage.is_dir(&root.join(TRASH_DIR).join(&e.id)));
+    if !removed.is_empty() {
+        save(storage, root, &kept)?;
+    }
+    Ok(removed)
+}
+
+/// Points entries of the trash in `new`, deleted from inside root `old`, to
+/// the same place in `new`.
+pub fn move_root(storage: &dyn Storage, old: &Path, new: &Path) -> Result<(), String> {
+    let mut entries = entries(storage, new)?;
+    let mut changed = false;
+    for entry in &mut entries {
+        if let Ok(relative) = entry.path.strip_prefix(old) {
+            entry.path = new.join(relative);
+            changed = true;
+        }
+    }
+    match changed {
+        true => save(storage, new, &entries),
+        false => Ok(()),
+    }
+}
+
+/// Trash directory of `root`, which is created if needed.
+pub fn dir(storage: &dyn Storage, root: &Path) -> Result<PathBuf, String> {
+    let dir = root.join(TRASH_DIR);
+    if !storage.is_dir(&dir) {
+        storage.create_dir(&dir)?;
+    }
+    Ok(dir)
+}
+
+/// Replaces the journal of the trash in `root` with `entries`.
+pub fn save(storage: &dyn Storage, root: &Path, entries: &[Entry]) -> Result<(), String> {
+    let dir = dir(storage, root)?;
+    storage.write(
+        &dir.join(JOURNAL_FILE),
+        &serde_json::to_string_pretty(entries).unwrap(),
+    )
+}
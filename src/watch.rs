@@ -0,0 +1,109 @@
+//! Watches project directories so long-running front-ends know when their
+//! in-memory list is stale. Events only name the project that changed, the
+//! caller decides when to `ProjectManager::reload`.
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::project::PROJECT_FILE;
+
+/// A change inside the directory of project `name`. `metadata` is set when
+/// the change was to its metadata file.
+#[derive(Debug)]
+pub struct Event {
+    pub name: String,
+    pub metadata: bool,
+}
+
+pub struct Watcher {
+    root: PathBuf,
+    external: HashMap<PathBuf, String>,
+    inner: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl Watcher {
+    /// Watches `root` and every project directory in it, plus the `external`
+    /// project directories(by name). Only direct children are watched so big
+    /// build directories don't exhaust the system's watch limit.
+    pub fn new(root: &Path, external: &HashMap<String, PathBuf>) -> Result<Self, String> {
+        let (tx, events) = mpsc::channel();
+        let mut inner = notify::recommended_watcher(tx)
+            .map_err(|e| format!("Couldn't start watcher: {}", e))?;
+        let mut dirs = vec![root.to_owned()];
+        for entry in std::fs::read_dir(root)
+            .map_err(|e| e.to_string())?
+            .flatten()
+        {
+            if entry.path().is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        dirs.extend(external.values().cloned());
+        for dir in dirs {
+            inner
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("Couldn't watch {:?}: {}", dir, e))?;
+        }
+        Ok(Watcher {
+            root: root.to_owned(),
+            external: external
+                .iter()
+                .map(|(name, path)| (path.clone(), name.clone()))
+                .collect(),
+            inner,
+            events,
+        })
+    }
+    /// Blocks until a project changes. Returns None once the watcher stopped.
+    pub fn recv(&mut self) -> Option<Event> {
+        loop {
+            let event = self.events.recv().ok()?;
+            if let Some(event) = self.handle(event) {
+                return Some(event);
+            }
+        }
+    }
+    /// Names of the projects changed since the last call, without blocking.
+    pub fn dirty(&mut self) -> HashSet<String> {
+        let mut res = HashSet::new();
+        while let Ok(event) = self.events.try_recv() {
+            if let Some(event) = self.handle(event) {
+                res.insert(event.name);
+            }
+        }
+        res
+    }
+    fn handle(&mut self, event: notify::Result<notify::Event>) -> Option<Event> {
+        let event = event.ok()?;
+        if matches!(event.kind, EventKind::Access(_)) {
+            return None;
+        }
+        let path = event.paths.first()?;
+        let metadata = path.file_name().is_some_and(|f| f == PROJECT_FILE);
+        let external = self.external.iter().find(|(dir, _)| path.starts_with(dir));
+        if let Some((_, name)) = external {
+            return Some(Event {
+                name: name.clone(),
+                metadata,
+            });
+        }
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let name = relative.components().next()?.as_os_str();
+        // new directories in root may become projects, so watch them as well
+        if matches!(event.kind, EventKind::Create(_))
+            && relative.components().count() == 1
+            && path.is_dir()
+        {
+            let _ = self.inner.watch(path, RecursiveMode::NonRecursive);
+        }
+        Some(Event {
+            name: name.to_string_lossy().into_owned(),
+            metadata,
+        })
+    }
+}
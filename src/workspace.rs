@@ -0,0 +1,42 @@
+//! Editor session files grouping several projects, currently VS Code's
+//! `.code-workspace` format.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+
+use crate::paths;
+
+pub const VSCODE_PRESET: &str = "vscode-workspace";
+const WORKSPACE_DIR: &str = "workspaces";
+
+/// Where the workspace of `names` is written when no output is given.
+pub fn default_file(names: &[&String]) -> Result<PathBuf, String> {
+    let dir =
+        paths::cache(WORKSPACE_DIR).ok_or("Couldn't retrieve cache location for your system")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Couldn't create {:?}: {}", dir, e))?;
+    Ok(dir.join(format!(
+        "{}.code-workspace",
+        names
+            .iter()
+            .map(|n| n.as_str())
+            .collect::<Vec<_>>()
+            .join("+")
+    )))
+}
+
+/// Writes a VS Code workspace containing every `(name, path)` pair as a folder.
+pub fn write(folders: &[(&String, PathBuf)], file: &Path) -> Result<(), String> {
+    let workspace = json!({
+        "folders": folders
+            .iter()
+            .map(|(name, path)| json!({"name": name, "path": path}))
+            .collect::<Vec<_>>(),
+        "settings": {},
+    });
+    fs::write(file, serde_json::to_string_pretty(&workspace).unwrap())
+        .map_err(|e| format!("Couldn't write {:?}: {}", file, e))
+}